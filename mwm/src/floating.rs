@@ -0,0 +1,223 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use log::debug;
+use mwm_xcb::component::{
+    Border, Floating, IsFocused, IsManaged, Monitor, PrefferedBorder, PrefferedSize, Size,
+    WindowType, WorkArea,
+};
+use mwm_xcb::request::{RequestBorder, RequestSize};
+use mwm_xcb::{monitor_for, Region, XcbStage};
+
+use crate::layout::Tile;
+use crate::monitor::OnMonitor;
+
+/// Floats windows which are not meant to be tiled and places them where their
+/// clients want them
+pub struct FloatingPlugin;
+
+impl Plugin for FloatingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToggleFloating>().add_system_set_to_stage(
+            XcbStage::Policy,
+            SystemSet::new()
+                .with_system(float_windows)
+                .with_system(toggle_floating)
+                .with_system(place_floating),
+        );
+    }
+}
+
+/// Requests the focused window to float or to get tiled again
+#[derive(Debug, Clone, Copy)]
+pub struct ToggleFloating;
+
+/// Marks windows floated for their [`WindowType`] rather than by the user
+#[derive(Component, Debug)]
+struct AutoFloating;
+
+/// Floats managed windows of types other than normal, docks and desktops, and
+/// tiles them again once their type changes back unless the user floated them
+fn float_windows(
+    query: Query<
+        (
+            Entity,
+            &WindowType,
+            Option<&Floating>,
+            Option<&AutoFloating>,
+        ),
+        (With<IsManaged>, Changed<WindowType>),
+    >,
+    mut commands: Commands,
+) {
+    for (entity, window_type, floating, auto_floating) in query.iter() {
+        let floats = !matches!(
+            window_type,
+            WindowType::Normal | WindowType::Dock | WindowType::Desktop
+        );
+        if floats && floating.is_none() {
+            commands
+                .entity(entity)
+                .insert(Floating)
+                .insert(AutoFloating);
+        } else if !floats && auto_floating.is_some() {
+            debug!("tile {entity:?} again as {window_type:?}");
+            commands
+                .entity(entity)
+                .remove::<Floating>()
+                .remove::<AutoFloating>();
+        }
+    }
+}
+
+/// Toggles the [`Floating`] marker of the focused window
+fn toggle_floating(
+    mut events: EventReader<ToggleFloating>,
+    query: Query<(Entity, Option<&Floating>), (With<IsManaged>, With<IsFocused>)>,
+    mut commands: Commands,
+) {
+    if events.iter().last().is_none() {
+        return;
+    }
+    for (entity, floating) in query.iter() {
+        debug!("toggle floating of {entity:?}");
+        let mut entity = commands.entity(entity);
+        match floating {
+            Some(_) => entity.remove::<Floating>(),
+            None => entity.insert(Floating),
+        };
+        // the user's choice sticks when the window type changes
+        entity.remove::<AutoFloating>();
+    }
+}
+
+/// Places windows which just started floating at their preferred geometry,
+/// the ones without a preferred position get centered on their monitor
+fn place_floating(
+    query: Query<
+        (
+            Entity,
+            &PrefferedSize,
+            Option<&PrefferedBorder>,
+            &Border,
+            Option<&OnMonitor>,
+        ),
+        Added<Floating>,
+    >,
+    monitors: Query<(Entity, &Monitor, &Size)>,
+    work_areas: Query<&WorkArea>,
+    mut commands: Commands,
+) {
+    for (entity, &PrefferedSize(size), preffered_border, &Border(border), on_monitor) in
+        query.iter()
+    {
+        // windows which never asked for a border keep theirs
+        let border = preffered_border.map_or(border, |&PrefferedBorder(border)| border);
+        let mut size = size;
+        let monitor = on_monitor
+            .map(|&OnMonitor(monitor)| monitor)
+            .or_else(|| monitor_for(size, &monitors));
+        if let Some(Ok(&WorkArea(area))) = monitor.map(|monitor| work_areas.get(monitor)) {
+            if size.x == 0 && size.y == 0 {
+                let (center, half) = (area.relative_center(), size.relative_center());
+                size = Region {
+                    x: area.x + center.x - half.x,
+                    y: area.y + center.y - half.y,
+                    ..size
+                };
+            }
+            size = size.clamp_within(&area);
+        }
+        debug!("float {entity:?} at {size:?}");
+        commands
+            .entity(entity)
+            .insert(RequestSize(size))
+            .insert(RequestBorder(border))
+            .remove::<Tile>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::event::Events;
+    use mwm_xcb::backend::{Backend, PropertyData};
+    use mwm_xcb::fake::FakeBackend;
+    use mwm_xcb::test_support::{self, map_window, run};
+    use mwm_xcb::Atom;
+    use xcb::Xid;
+
+    use super::*;
+
+    const REGION: Region = Region { x: 0, y: 0, w: 640, h: 480 };
+
+    fn app(fake: &FakeBackend) -> App {
+        let mut app = test_support::app(fake);
+        app.add_plugin(FloatingPlugin);
+        run(&mut app, 1);
+        app
+    }
+
+    fn set_type(fake: &FakeBackend, window: xcb::x::Window, window_type: Atom) {
+        let atom = fake.atom(window_type).resource_id();
+        fake.set_property(window, Atom::NetWmWindowType, PropertyData::U32(vec![atom]));
+    }
+
+    fn is_floating(app: &App, entity: Entity) -> bool {
+        app.world.entity(entity).contains::<Floating>()
+    }
+
+    #[test]
+    fn floats_transient_windows_as_dialogs() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let parent = fake.create_window(REGION, false);
+        let window = fake.create_window(REGION, false);
+        fake.set_property(
+            window,
+            Atom::WmTransientFor,
+            PropertyData::U32(vec![parent.resource_id()]),
+        );
+        let entity = map_window(&mut app, &fake, window);
+
+        assert_eq!(
+            app.world.entity(entity).get::<WindowType>(),
+            Some(&WindowType::Dialog)
+        );
+        assert!(is_floating(&app, entity));
+    }
+
+    #[test]
+    fn tiles_windows_again_once_they_turn_normal() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(REGION, false);
+        set_type(&fake, window, Atom::NetWindowTypeUtility);
+        let entity = map_window(&mut app, &fake, window);
+        assert!(is_floating(&app, entity));
+
+        set_type(&fake, window, Atom::NetWindowTypeNormal);
+        run(&mut app, 2);
+        assert!(!is_floating(&app, entity));
+    }
+
+    #[test]
+    fn keeps_windows_floated_by_the_user_floating() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(REGION, false);
+        let entity = map_window(&mut app, &fake, window);
+        assert!(!is_floating(&app, entity));
+
+        app.world.entity_mut(entity).insert(IsFocused);
+        app.world
+            .resource_mut::<Events<ToggleFloating>>()
+            .send(ToggleFloating);
+        run(&mut app, 1);
+        assert!(is_floating(&app, entity));
+
+        set_type(&fake, window, Atom::NetWindowTypeDialog);
+        run(&mut app, 2);
+        set_type(&fake, window, Atom::NetWindowTypeNormal);
+        run(&mut app, 2);
+        assert!(is_floating(&app, entity));
+    }
+}
@@ -0,0 +1,753 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use log::debug;
+use mwm_xcb::component::{
+    Floating, Fullscreen, IgnoreSizeHints, IsManaged, IsMapped, NetWmState, WindowType, WmClass,
+    WorkArea,
+};
+use mwm_xcb::request::{RequestBorder, RequestSize};
+use mwm_xcb::{Region, XcbStage, XcbSystem};
+
+use crate::monitor::{FocusedMonitor, OnMonitor};
+use crate::tree::Tree;
+use crate::workspace::{OnWorkspace, Workspace};
+
+/// Tiles the windows of shown workspaces into their monitor's work area
+pub struct LayoutPlugin {
+    /// Space between windows and around the work area
+    pub gap: u32,
+    /// Border width of tiled windows
+    pub border: u16,
+    /// Creates the layouts every workspace cycles through, the first one is
+    /// active initially
+    pub layouts: fn() -> Vec<Box<dyn Layout>>,
+    /// `WM_CLASS` instances or classes and the size hints their tiled windows
+    /// ignore, e.g. increments to fill their tile
+    pub ignore_size_hints: Vec<(String, IgnoreSizeHints)>,
+}
+
+impl Default for LayoutPlugin {
+    fn default() -> Self {
+        Self {
+            gap: 0,
+            border: 1,
+            layouts: default_layouts,
+            ignore_size_hints: Vec::new(),
+        }
+    }
+}
+
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TileConfig { gap: self.gap, border: self.border })
+            .insert_resource(NewLayouts(self.layouts))
+            .insert_resource(SizeHintRules(self.ignore_size_hints.clone()))
+            .add_event::<LayoutCommand>()
+            .add_system_set_to_stage(
+                XcbStage::Policy,
+                SystemSet::new()
+                    .with_system(init_layouts)
+                    .with_system(order_windows)
+                    .with_system(command_layouts)
+                    .with_system(
+                        tile_workspaces
+                            .after(command_layouts)
+                            .after(XcbSystem::Fullscreen),
+                    ),
+            )
+            // NOTE runs after the policy so floating toggled there shows up
+            // in the removed components, the tiled sizes follow next frame
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                ignore_size_hints.before(XcbSystem::ConstrainSize),
+            );
+    }
+}
+
+/// Gap and border width shared by all tiled windows
+#[derive(Debug, Clone, Copy)]
+pub struct TileConfig {
+    pub gap: u32,
+    pub border: u16,
+}
+
+impl TileConfig {
+    /// Shrinks the work area so windows shrunk by [`TileConfig::window_gap`]
+    /// end up a full gap away from its edges
+    pub(crate) fn area_gap(&self, area: Region) -> Region {
+        area.shrink(self.gap - self.gap / 2, self.gap / 2)
+    }
+
+    /// Shrinks the region a window got arranged into, neighbouring windows end
+    /// up a full gap apart even for odd gaps
+    pub(crate) fn window_gap(&self, region: Region) -> Region {
+        region.shrink(self.gap / 2, self.gap - self.gap / 2)
+    }
+}
+
+/// Arranges tiled windows within an area
+pub trait Layout: std::fmt::Debug + Send + Sync + 'static {
+    /// Name to select the layout by
+    fn name(&self) -> &str;
+
+    /// Returns the regions of the windows in tiling order, borders and gaps
+    /// are not accounted for
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region>;
+
+    /// Reacts to commands adjusting the layout
+    fn adjust(&mut self, _command: &LayoutCommand) {}
+}
+
+/// The layouts of a workspace and the one in use
+#[derive(Component, Debug)]
+pub struct Layouts {
+    pub layouts: Vec<Box<dyn Layout>>,
+    pub active: usize,
+}
+
+impl Layouts {
+    pub fn active(&self) -> Option<&dyn Layout> {
+        self.layouts.get(self.active).map(Box::as_ref)
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Box<dyn Layout>> {
+        self.layouts.get_mut(self.active)
+    }
+}
+
+/// Creates the [`Layouts`] of new workspaces
+struct NewLayouts(fn() -> Vec<Box<dyn Layout>>);
+
+/// Classes of windows and the size hints they ignore while tiled
+struct SizeHintRules(Vec<(String, IgnoreSizeHints)>);
+
+impl SizeHintRules {
+    /// Size hints ignored by windows of the class, the ones of every matching
+    /// rule combined
+    fn ignored(&self, class: &WmClass) -> IgnoreSizeHints {
+        self.0
+            .iter()
+            .filter(|(rule, _)| *rule == class.instance || *rule == class.class)
+            .fold(IgnoreSizeHints::empty(), |ignored, &(_, hints)| {
+                ignored | hints
+            })
+    }
+}
+
+/// Master-stack, monocle, grid, both stacks and centered master
+pub fn default_layouts() -> Vec<Box<dyn Layout>> {
+    vec![
+        Box::new(MasterStack::default()),
+        Box::new(Monocle),
+        Box::new(Grid),
+        Box::new(HorizontalStack),
+        Box::new(VerticalStack),
+        Box::new(CenteredMaster::default()),
+    ]
+}
+
+/// Keeps the share of the master area within bounds, leaving room for both
+/// the masters and the stack
+fn clamp_ratio(ratio: f32) -> f32 {
+    ratio.clamp(0.1, 0.9)
+}
+
+/// Applies ratio and master count commands
+fn adjust_masters(ratio: &mut f32, masters: &mut usize, command: &LayoutCommand) {
+    match *command {
+        LayoutCommand::Ratio(delta) => *ratio = clamp_ratio(*ratio + delta),
+        LayoutCommand::Masters(delta) => *masters = masters.saturating_add_signed(delta),
+        _ => {},
+    }
+}
+
+/// The first windows share the master area while the others get stacked next
+/// to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasterStack {
+    /// Share of the width taken by the master area
+    pub ratio: f32,
+    /// Number of windows in the master area
+    pub masters: usize,
+}
+
+impl Default for MasterStack {
+    fn default() -> Self {
+        Self { ratio: 0.55, masters: 1 }
+    }
+}
+
+impl Layout for MasterStack {
+    fn name(&self) -> &str {
+        "master-stack"
+    }
+
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region> {
+        let count = windows.len();
+        let masters = self.masters.min(count);
+        let stacked = count - masters;
+        if masters == 0 || stacked == 0 {
+            return area.split_rows(count as u32);
+        }
+        let width = (area.w as f32 * clamp_ratio(self.ratio)).round() as u32;
+        let (master, stack) = (Region { w: width, ..area }, Region {
+            x: area.x + width as i32,
+            w: area.w - width,
+            ..area
+        });
+        let mut regions = master.split_rows(masters as u32);
+        regions.extend(stack.split_rows(stacked as u32));
+        regions
+    }
+
+    fn adjust(&mut self, command: &LayoutCommand) {
+        adjust_masters(&mut self.ratio, &mut self.masters, command);
+    }
+}
+
+/// Every window takes the whole area
+#[derive(Debug, Clone, Copy)]
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &str {
+        "monocle"
+    }
+
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region> {
+        vec![area; windows.len()]
+    }
+}
+
+/// Windows in rows of about as many columns, the last row might hold fewer
+/// and wider windows
+#[derive(Debug, Clone, Copy)]
+pub struct Grid;
+
+impl Layout for Grid {
+    fn name(&self) -> &str {
+        "grid"
+    }
+
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region> {
+        let count = windows.len();
+        let columns = (1..).find(|c| c * c >= count).unwrap_or(1);
+        let rows = count.div_ceil(columns.max(1));
+        area.split_rows(rows as u32)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(row, region)| {
+                let in_row = columns.min(count - row * columns);
+                region.split_columns(in_row as u32)
+            })
+            .collect()
+    }
+}
+
+/// Windows side by side in columns of equal width
+#[derive(Debug, Clone, Copy)]
+pub struct HorizontalStack;
+
+impl Layout for HorizontalStack {
+    fn name(&self) -> &str {
+        "horizontal-stack"
+    }
+
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region> {
+        area.split_columns(windows.len() as u32)
+    }
+}
+
+/// Windows on top of each other in rows of equal height
+#[derive(Debug, Clone, Copy)]
+pub struct VerticalStack;
+
+impl Layout for VerticalStack {
+    fn name(&self) -> &str {
+        "vertical-stack"
+    }
+
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region> {
+        area.split_rows(windows.len() as u32)
+    }
+}
+
+/// The master area in the middle with the other windows stacked right and
+/// left of it, alternately
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CenteredMaster {
+    /// Share of the width taken by the master area
+    pub ratio: f32,
+    /// Number of windows in the master area
+    pub masters: usize,
+}
+
+impl Default for CenteredMaster {
+    fn default() -> Self {
+        Self { ratio: 0.5, masters: 1 }
+    }
+}
+
+impl Layout for CenteredMaster {
+    fn name(&self) -> &str {
+        "centered-master"
+    }
+
+    fn arrange(&self, windows: &[Entity], area: Region) -> Vec<Region> {
+        let count = windows.len();
+        let masters = self.masters.min(count);
+        let stacked = count - masters;
+        if masters == 0 || stacked == 0 {
+            return area.split_rows(count as u32);
+        }
+        let (right, left) = (stacked.div_ceil(2), stacked / 2);
+        let width = (area.w as f32 * clamp_ratio(self.ratio)).round() as u32;
+        // without windows on the left the master area takes its space
+        let side = (area.w - width) / if left == 0 { 1 } else { 2 };
+        let left_w = if left == 0 { 0 } else { side };
+        let master = Region {
+            x: area.x + left_w as i32,
+            w: area.w - left_w - side,
+            ..area
+        };
+        let right_area = Region {
+            x: master.x + master.w as i32,
+            w: side,
+            ..area
+        };
+        let left_area = Region { w: left_w, ..area };
+
+        let mut right = right_area.split_rows(right as u32).into_iter();
+        let mut left = left_area.split_rows(left as u32).into_iter();
+        let mut regions = master.split_rows(masters as u32);
+        for i in 0..stacked {
+            let next = if i % 2 == 0 {
+                right.next()
+            } else {
+                left.next()
+            };
+            regions.extend(next);
+        }
+        regions
+    }
+
+    fn adjust(&mut self, command: &LayoutCommand) {
+        adjust_masters(&mut self.ratio, &mut self.masters, command);
+    }
+}
+
+/// Requests changing the layout of the workspace on the focused monitor
+#[derive(Debug, Clone)]
+pub enum LayoutCommand {
+    /// Grows the master area by the given share of the width
+    Ratio(f32),
+    /// Adds the given number of windows to the master area
+    Masters(isize),
+    /// Switches to the next layout
+    Next,
+    /// Switches to the previous layout
+    Previous,
+    /// Switches to the layout with the given name
+    Select(String),
+}
+
+/// Position of a window in the tiling order, windows managed earlier come
+/// first
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileOrder(pub u64);
+
+/// Region a window got tiled into, borders included
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile(pub Region);
+
+fn init_layouts(
+    query: Query<Entity, (With<Workspace>, Without<Layouts>)>,
+    new_layouts: Res<NewLayouts>,
+    mut commands: Commands,
+) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(Layouts { layouts: (new_layouts.0)(), active: 0 });
+    }
+}
+
+/// Appends newly managed windows to the tiling order
+fn order_windows(
+    query: Query<Entity, (With<IsManaged>, Without<TileOrder>)>,
+    mut next: Local<u64>,
+    mut commands: Commands,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(TileOrder(*next));
+        *next += 1;
+    }
+}
+
+/// Marks tiled windows matching a [`SizeHintRules`] class with the
+/// [`IgnoreSizeHints`] of the rules, floating windows always keep to their
+/// hints. Only looks at windows whose class changed or which started or
+/// stopped floating. Windows whose ignored hints changed get tiled anew
+fn ignore_size_hints(
+    rules: Res<SizeHintRules>,
+    changed: Query<Entity, (With<IsManaged>, Or<(Changed<WmClass>, Added<Floating>)>)>,
+    unfloated: RemovedComponents<Floating>,
+    query: Query<(&WmClass, Option<&Floating>, Option<&IgnoreSizeHints>), With<IsManaged>>,
+    mut commands: Commands,
+) {
+    for entity in changed.iter().chain(unfloated.iter()) {
+        let (class, floating, current) = match query.get(entity) {
+            Ok(window) => window,
+            Err(_) => continue,
+        };
+        let ignored = match floating {
+            Some(_) => IgnoreSizeHints::empty(),
+            None => rules.ignored(class),
+        };
+        if current.copied().unwrap_or_default() == ignored {
+            continue;
+        }
+        debug!("window of {class:?} ignores size hints {ignored:?}");
+        let mut entity = commands.entity(entity);
+        entity.remove::<Tile>();
+        if ignored.is_empty() {
+            entity.remove::<IgnoreSizeHints>();
+        } else {
+            entity.insert(ignored);
+        }
+    }
+}
+
+/// Applies [`LayoutCommand`]s to the workspace on the focused monitor
+fn command_layouts(
+    mut events: EventReader<LayoutCommand>,
+    mut workspaces: Query<(&OnMonitor, &mut Layouts), With<Workspace>>,
+    focused: Query<Entity, With<FocusedMonitor>>,
+) {
+    let monitor = match focused.iter().next() {
+        Some(monitor) => OnMonitor(monitor),
+        None => return,
+    };
+    let mut layouts = match workspaces.iter_mut().find(|(&m, _)| m == monitor) {
+        Some((_, layouts)) => layouts,
+        None => return,
+    };
+    let count = layouts.layouts.len();
+    if count == 0 {
+        return;
+    }
+    for e in events.iter() {
+        match e {
+            LayoutCommand::Next => layouts.active = (layouts.active + 1) % count,
+            LayoutCommand::Previous => layouts.active = (layouts.active + count - 1) % count,
+            LayoutCommand::Select(name) => {
+                match layouts.layouts.iter().position(|l| l.name() == name) {
+                    Some(active) => layouts.active = active,
+                    None => continue,
+                }
+            },
+            command => {
+                if let Some(layout) = layouts.active_mut() {
+                    layout.adjust(command);
+                }
+            },
+        }
+        debug!("layout {:?}", layouts.active());
+    }
+}
+
+/// Tiles the mapped normal windows which don't float of every shown workspace
+/// into the work area of its monitor, unless the workspace is tiled manually
+fn tile_workspaces(
+    config: Res<TileConfig>,
+    workspaces: Query<(Entity, &Layouts, &OnMonitor), (With<Workspace>, Without<Tree>)>,
+    monitors: Query<&WorkArea>,
+    windows: Query<
+        (
+            Entity,
+            &OnWorkspace,
+            &TileOrder,
+            &WindowType,
+            Option<&NetWmState>,
+            Option<&Tile>,
+        ),
+        (
+            With<IsManaged>,
+            With<IsMapped>,
+            Without<Fullscreen>,
+            Without<Floating>,
+        ),
+    >,
+    mut commands: Commands,
+) {
+    for (workspace, layouts, &OnMonitor(monitor)) in workspaces.iter() {
+        let layout = match layouts.active() {
+            Some(layout) => layout,
+            None => continue,
+        };
+        let area = match monitors.get(monitor) {
+            Ok(&WorkArea(area)) => config.area_gap(area),
+            Err(_) => continue,
+        };
+        let mut tiled = windows
+            .iter()
+            .filter(|&(_, &OnWorkspace(w), _, &window_type, _, _)| {
+                w == workspace && window_type == WindowType::Normal
+            })
+            .collect::<Vec<_>>();
+        tiled.sort_by_key(|&(_, _, &order, ..)| order);
+
+        let entities = tiled.iter().map(|&(entity, ..)| entity).collect::<Vec<_>>();
+        let regions = layout.arrange(&entities, area);
+        for ((entity, _, _, _, state, tile), region) in tiled.into_iter().zip(regions) {
+            let region = maximize(region, area, state);
+            self::tile(
+                entity,
+                config.window_gap(region),
+                tile,
+                &config,
+                &mut commands,
+            );
+        }
+    }
+}
+
+/// Stretches the region a window got arranged into across the area along the
+/// axes the window is maximized in, the other windows keep their regions
+pub(crate) fn maximize(region: Region, area: Region, state: Option<&NetWmState>) -> Region {
+    let state = state.copied().unwrap_or_default();
+    let mut region = region;
+    if state.contains(NetWmState::MAXIMIZED_HORZ) {
+        region.x = area.x;
+        region.w = area.w;
+    }
+    if state.contains(NetWmState::MAXIMIZED_VERT) {
+        region.y = area.y;
+        region.h = area.h;
+    }
+    region
+}
+
+/// Requests the window to fill the region with its border unless it already
+/// got tiled there
+pub(crate) fn tile(
+    entity: Entity,
+    region: Region,
+    tile: Option<&Tile>,
+    config: &TileConfig,
+    commands: &mut Commands,
+) {
+    if tile == Some(&Tile(region)) {
+        return;
+    }
+    let border = u32::from(config.border);
+    let size = Region {
+        w: region.w.saturating_sub(2 * border).max(1),
+        h: region.h.saturating_sub(2 * border).max(1),
+        ..region
+    };
+    commands
+        .entity(entity)
+        .insert(Tile(region))
+        .insert(RequestSize(size))
+        .insert(RequestBorder(config.border));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::event::Events;
+    use mwm_xcb::backend::PropertyData;
+    use mwm_xcb::component::IsFocused;
+    use mwm_xcb::fake::FakeBackend;
+    use mwm_xcb::test_support::{self, map_window, run, single_monitor};
+    use mwm_xcb::Atom;
+
+    use super::*;
+    use crate::floating::{FloatingPlugin, ToggleFloating};
+    use crate::monitor::MonitorPlugin;
+    use crate::workspace::WorkspacePlugin;
+
+    const AREA: Region = Region { x: 0, y: 0, w: 1000, h: 600 };
+    const SCREEN: Region = Region { x: 0, y: 0, w: 1920, h: 1080 };
+
+    fn windows(count: u32) -> Vec<Entity> {
+        (0..count).map(Entity::from_raw).collect()
+    }
+
+    #[test]
+    fn master_stack_puts_masters_left_of_the_stack() {
+        let layout = MasterStack::default();
+        assert_eq!(layout.arrange(&windows(3), AREA), vec![
+            Region { x: 0, y: 0, w: 550, h: 600 },
+            Region { x: 550, y: 0, w: 450, h: 300 },
+            Region { x: 550, y: 300, w: 450, h: 300 },
+        ]);
+    }
+
+    #[test]
+    fn master_stack_without_stack_fills_the_area() {
+        let layout = MasterStack { ratio: 0.55, masters: 2 };
+        assert_eq!(layout.arrange(&windows(1), AREA), vec![AREA]);
+        assert_eq!(layout.arrange(&windows(2), AREA), AREA.split_rows(2));
+        let layout = MasterStack { ratio: 0.55, masters: 0 };
+        assert_eq!(layout.arrange(&windows(2), AREA), AREA.split_rows(2));
+        assert_eq!(layout.arrange(&[], AREA), vec![]);
+    }
+
+    #[test]
+    fn master_stack_adjusts_within_bounds() {
+        let mut layout = MasterStack::default();
+        layout.adjust(&LayoutCommand::Ratio(1.0));
+        layout.adjust(&LayoutCommand::Masters(-5));
+        assert_eq!(layout, MasterStack { ratio: 0.9, masters: 0 });
+        layout.adjust(&LayoutCommand::Ratio(-1.0));
+        layout.adjust(&LayoutCommand::Masters(2));
+        assert_eq!(layout, MasterStack { ratio: 0.1, masters: 2 });
+    }
+
+    #[test]
+    fn clamps_ratios_set_out_of_bounds() {
+        let layout = MasterStack { ratio: 1.5, masters: 1 };
+        assert_eq!(layout.arrange(&windows(2), AREA), vec![
+            Region { x: 0, y: 0, w: 900, h: 600 },
+            Region { x: 900, y: 0, w: 100, h: 600 },
+        ]);
+        let layout = CenteredMaster { ratio: -1.0, masters: 1 };
+        assert_eq!(layout.arrange(&windows(3), AREA), vec![
+            Region { x: 450, y: 0, w: 100, h: 600 },
+            Region { x: 550, y: 0, w: 450, h: 600 },
+            Region { x: 0, y: 0, w: 450, h: 600 },
+        ]);
+    }
+
+    #[test]
+    fn grid_widens_the_last_row() {
+        assert_eq!(Grid.arrange(&windows(3), AREA), vec![
+            Region { x: 0, y: 0, w: 500, h: 300 },
+            Region { x: 500, y: 0, w: 500, h: 300 },
+            Region { x: 0, y: 300, w: 1000, h: 300 },
+        ]);
+        assert_eq!(Grid.arrange(&windows(1), AREA), vec![AREA]);
+        assert_eq!(Grid.arrange(&[], AREA), vec![]);
+    }
+
+    #[test]
+    fn grid_fills_square_counts() {
+        let regions = Grid.arrange(&windows(9), AREA);
+        assert_eq!(regions.len(), 9);
+        assert!(regions
+            .iter()
+            .all(|r| r.h == 200 && (333..=334).contains(&r.w)));
+    }
+
+    #[test]
+    fn centered_master_alternates_stacked_windows_right_and_left() {
+        let layout = CenteredMaster::default();
+        assert_eq!(layout.arrange(&windows(4), AREA), vec![
+            Region { x: 250, y: 0, w: 500, h: 600 },
+            Region { x: 750, y: 0, w: 250, h: 300 },
+            Region { x: 0, y: 0, w: 250, h: 600 },
+            Region { x: 750, y: 300, w: 250, h: 300 },
+        ]);
+    }
+
+    #[test]
+    fn centered_master_takes_the_left_without_windows_there() {
+        let layout = CenteredMaster::default();
+        assert_eq!(layout.arrange(&windows(2), AREA), vec![
+            Region { x: 0, y: 0, w: 500, h: 600 },
+            Region { x: 500, y: 0, w: 500, h: 600 },
+        ]);
+        assert_eq!(layout.arrange(&windows(1), AREA), vec![AREA]);
+    }
+
+    #[test]
+    fn maximizes_along_the_requested_axes() {
+        let region = Region { x: 500, y: 300, w: 500, h: 300 };
+        let (vert, horz) = (NetWmState::MAXIMIZED_VERT, NetWmState::MAXIMIZED_HORZ);
+        assert_eq!(maximize(region, AREA, None), region);
+        assert_eq!(maximize(region, AREA, Some(&NetWmState::ABOVE)), region);
+        assert_eq!(maximize(region, AREA, Some(&vert)), Region {
+            y: 0,
+            h: 600,
+            ..region
+        });
+        assert_eq!(maximize(region, AREA, Some(&horz)), Region {
+            x: 0,
+            w: 1000,
+            ..region
+        });
+        assert_eq!(maximize(region, AREA, Some(&(vert | horz))), AREA);
+    }
+
+    #[test]
+    fn keeps_full_gaps_between_and_around_windows() {
+        let config = TileConfig { gap: 5, border: 0 };
+        let area = config.area_gap(AREA);
+        let regions = MasterStack::default()
+            .arrange(&windows(2), area)
+            .into_iter()
+            .map(|region| config.window_gap(region))
+            .collect::<Vec<_>>();
+        let (master, stack) = (regions[0], regions[1]);
+        assert_eq!((master.x, master.y), (5, 5));
+        assert_eq!(stack.x - (master.x + master.w as i32), 5);
+        assert_eq!(AREA.w as i32 - (stack.x + stack.w as i32), 5);
+        assert_eq!(AREA.h as i32 - (stack.y + stack.h as i32), 5);
+    }
+
+    #[test]
+    fn ignores_size_hints_of_tiled_windows_of_listed_classes() {
+        let fake = FakeBackend::new();
+        single_monitor(&fake, SCREEN);
+        let mut app = test_support::app(&fake);
+        app.add_plugin(MonitorPlugin)
+            .add_plugin(WorkspacePlugin::default())
+            .add_plugin(FloatingPlugin)
+            .add_plugin(LayoutPlugin {
+                ignore_size_hints: vec![("Terminal".into(), IgnoreSizeHints::INCREMENT)],
+                ..LayoutPlugin::default()
+            });
+        run(&mut app, 3);
+        let window = fake.create_window(AREA, false);
+        fake.set_property(
+            window,
+            Atom::WmClass,
+            PropertyData::U8(b"term\0Terminal\0".to_vec()),
+        );
+        // maximum size 1000x5000 in steps of 13x17
+        let mut hints = vec![0; 18];
+        hints[0] = (1 << 5) | (1 << 6);
+        hints[7..11].copy_from_slice(&[1000, 5000, 13, 17]);
+        fake.set_property(window, Atom::WmNormalHints, PropertyData::U32(hints));
+        let entity = map_window(&mut app, &fake, window);
+        // the tile less the border, capped to the maximum width but not
+        // stepped down to a multiple of 17 in height
+        let tiled = Region { x: 0, y: 0, w: 1000, h: 1078 };
+        assert_eq!(fake.window(window).unwrap().region, tiled);
+
+        app.world.entity_mut(entity).insert(IsFocused);
+        let toggle = |app: &mut App| {
+            app.world
+                .resource_mut::<Events<ToggleFloating>>()
+                .send(ToggleFloating);
+            run(app, 3);
+            app.world.entity(entity).get::<IgnoreSizeHints>().copied()
+        };
+        assert_eq!(toggle(&mut app), None);
+        assert_eq!(toggle(&mut app), Some(IgnoreSizeHints::INCREMENT));
+        assert_eq!(fake.window(window).unwrap().region, tiled);
+
+        // windows of classes without rules get tiled anew keeping to them
+        let class = |app: &mut App, class: &[u8]| {
+            fake.set_property(window, Atom::WmClass, PropertyData::U8(class.to_vec()));
+            run(app, 3);
+            fake.window(window).unwrap().region
+        };
+        assert_eq!(class(&mut app, b"term\0Other\0"), Region {
+            h: 1071,
+            ..tiled
+        });
+        assert_eq!(class(&mut app, b"term\0Terminal\0"), tiled);
+    }
+}
@@ -0,0 +1,7 @@
+#![allow(clippy::type_complexity)]
+
+pub mod floating;
+pub mod layout;
+pub mod monitor;
+pub mod tree;
+pub mod workspace;
@@ -1,31 +1,85 @@
+#![allow(clippy::type_complexity)]
+
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use mwm_xcb::component::{IsManaged, Window};
-use mwm_xcb::event as ev;
-use mwm_xcb::request::RequestMap;
+use mwm::{floating, layout, monitor, tree, workspace};
+use mwm_xcb::component::{Fullscreen, IsManaged, NetWmState};
+use mwm_xcb::request::{RequestClose, RequestFocus};
+use mwm_xcb::{event as ev, Atom, NetSupported, XcbSystem};
 
 fn main() {
     pretty_env_logger::init();
 
-    App::new()
-        .add_plugin(mwm_xcb::XcbPlugin::default())
-        .add_system(map_all_windows)
+    let mut app = App::new();
+    app.add_plugin(mwm_xcb::XcbPlugin::default())
+        .add_plugin(monitor::MonitorPlugin)
+        .add_plugin(workspace::WorkspacePlugin::default())
+        .add_plugin(layout::LayoutPlugin::default())
+        .add_plugin(tree::TreePlugin::default())
+        .add_plugin(floating::FloatingPlugin)
+        .add_system(activate_windows.after(XcbSystem::DecodeClientMessages))
+        .add_system(close_windows.after(XcbSystem::DecodeClientMessages))
+        .add_system(change_wm_states.after(XcbSystem::DecodeClientMessages))
         .set_runner(|mut app| loop {
             app.update();
-        })
-        .run()
+        });
+    app.world
+        .resource_mut::<NetSupported>()
+        .insert(&[Atom::NetCloseWindow]);
+    app.run()
+}
+
+/// Focuses windows activated through `_NET_ACTIVE_WINDOW`
+fn activate_windows(
+    mut events: EventReader<ev::ActivateWindowRequest>,
+    query: Query<(), With<IsManaged>>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        if query.get(e.entity).is_ok() {
+            commands.entity(e.entity).insert(RequestFocus);
+        }
+    }
+}
+
+/// Closes windows through `_NET_CLOSE_WINDOW`
+fn close_windows(
+    mut events: EventReader<ev::CloseWindowRequest>,
+    query: Query<(), With<IsManaged>>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        if query.get(e.entity).is_ok() {
+            commands.entity(e.entity).insert(RequestClose);
+        }
+    }
 }
 
-fn map_all_windows(
-    mut events: EventReader<ev::MapRequest>,
-    query: Query<(Entity, &Window), With<IsManaged>>,
+/// Applies `_NET_WM_STATE` client messages, fullscreen is requested through
+/// the [`Fullscreen`] marker while hidden is up to us
+fn change_wm_states(
+    mut events: EventReader<ev::WmStateChangeRequest>,
+    mut query: Query<(&mut NetWmState, Option<&Fullscreen>), With<IsManaged>>,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window) in query.iter() {
-            if window == e.window() {
-                commands.entity(entity).insert(RequestMap::Map);
-            }
+        let (mut state, fullscreen) = match query.get_mut(e.entity) {
+            Ok(query) => query,
+            Err(_) => continue,
+        };
+        let mut requested = *state;
+        requested.apply(e.action, NetWmState::from_atoms(&e.states));
+
+        if requested.contains(NetWmState::FULLSCREEN) && fullscreen.is_none() {
+            commands.entity(e.entity).insert(Fullscreen);
+        } else if !requested.contains(NetWmState::FULLSCREEN) && fullscreen.is_some() {
+            commands.entity(e.entity).remove::<Fullscreen>();
+        }
+
+        let managed_by_us = NetWmState::FULLSCREEN | NetWmState::HIDDEN;
+        let requested = (requested - managed_by_us) | (*state & managed_by_us);
+        if requested != *state {
+            *state = requested;
         }
     }
 }
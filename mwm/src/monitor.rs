@@ -0,0 +1,173 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use log::debug;
+use mwm_xcb::component::{IsFocused, IsManaged, Monitor, Size, Window, WorkArea};
+use mwm_xcb::request::RequestSize;
+use mwm_xcb::{monitor_for, Region, XcbStage};
+
+/// Assigns windows to monitors, moves them off monitors which disappear and
+/// keeps track of the focused monitor
+pub struct MonitorPlugin;
+
+impl Plugin for MonitorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FocusMonitor>().add_system_set_to_stage(
+            XcbStage::Policy,
+            SystemSet::new()
+                .with_system(assign_monitors)
+                .with_system(focus_monitors)
+                .with_system(migrate_orphans)
+                .with_system(restore_migrated.after(migrate_orphans)),
+        );
+    }
+}
+
+/// Monitor entity a window or workspace is on
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnMonitor(pub Entity);
+
+/// Marks the monitor commands like viewing a workspace apply to
+#[derive(Component, Debug)]
+pub struct FocusedMonitor;
+
+/// Requests focusing the monitor with the given index in [`sorted_monitors`]
+#[derive(Debug, Clone, Copy)]
+pub struct FocusMonitor(pub usize);
+
+/// Marks entities moved off a monitor which disappeared, they go back once a
+/// monitor with the same name shows up again
+#[derive(Component, Debug)]
+pub struct Migrated {
+    /// Name of the monitor the entity was on
+    pub home: String,
+    /// Geometry of the window on that monitor
+    pub size: Option<Region>,
+}
+
+/// Returns the monitors ordered left to right, then top to bottom
+pub fn sorted_monitors(monitors: &Query<(Entity, &Monitor, &Size)>) -> Vec<Entity> {
+    let mut sorted = monitors
+        .iter()
+        .map(|(entity, _, &Size(size))| (entity, size))
+        .collect::<Vec<_>>();
+    sorted.sort_by_key(|&(_, size)| (size.x, size.y));
+    sorted.into_iter().map(|(entity, _)| entity).collect()
+}
+
+/// Puts managed windows on the monitor they are on
+fn assign_monitors(
+    query: Query<(Entity, &Size), (With<IsManaged>, Without<OnMonitor>)>,
+    monitors: Query<(Entity, &Monitor, &Size)>,
+    mut commands: Commands,
+) {
+    for (entity, &Size(size)) in query.iter() {
+        if let Some(monitor) = monitor_for(size, &monitors) {
+            commands.entity(entity).insert(OnMonitor(monitor));
+        }
+    }
+}
+
+/// Moves the [`FocusedMonitor`] marker on request or to the monitor of the
+/// newly focused window, falling back to the primary monitor
+fn focus_monitors(
+    mut events: EventReader<FocusMonitor>,
+    windows: Query<&OnMonitor, (With<Window>, Added<IsFocused>)>,
+    monitors: Query<(Entity, &Monitor, &Size)>,
+    focused: Query<Entity, With<FocusedMonitor>>,
+    mut commands: Commands,
+) {
+    let current = focused.iter().next();
+    let target = events
+        .iter()
+        .last()
+        .and_then(|&FocusMonitor(index)| sorted_monitors(&monitors).get(index).copied())
+        .or_else(|| windows.iter().next().map(|&OnMonitor(monitor)| monitor))
+        .or_else(|| match current {
+            Some(_) => None,
+            None => monitors
+                .iter()
+                .find(|(_, monitor, _)| monitor.primary)
+                .or_else(|| monitors.iter().next())
+                .map(|(entity, _, _)| entity),
+        });
+    let target = match target {
+        Some(target) if Some(target) != current && monitors.get(target).is_ok() => target,
+        _ => return,
+    };
+    debug!("focus monitor {target:?}");
+    if let Some(current) = current {
+        commands.entity(current).remove::<FocusedMonitor>();
+    }
+    commands.entity(target).insert(FocusedMonitor);
+}
+
+/// Moves entities whose monitor disappeared to the primary monitor and fits
+/// windows into its work area
+fn migrate_orphans(
+    mut query: Query<(Entity, &mut OnMonitor, Option<&Size>, Option<&Migrated>)>,
+    monitors: Query<(Entity, &Monitor, &WorkArea)>,
+    removed: RemovedComponents<Monitor>,
+    mut names: Local<Vec<(Entity, String)>>,
+    mut commands: Commands,
+) {
+    // NOTE names are remembered as the monitors are already gone by the time
+    // their removal is seen
+    let removed = removed.iter().collect::<Vec<_>>();
+    let gone = names
+        .iter()
+        .filter(|(entity, _)| removed.contains(entity))
+        .cloned()
+        .collect::<Vec<_>>();
+    *names = monitors
+        .iter()
+        .map(|(entity, monitor, _)| (entity, monitor.name.clone()))
+        .collect();
+    if gone.is_empty() {
+        return;
+    }
+
+    let (target, _, &WorkArea(area)) = match monitors
+        .iter()
+        .find(|(_, monitor, _)| monitor.primary)
+        .or_else(|| monitors.iter().next())
+    {
+        Some(target) => target,
+        None => return,
+    };
+    for (entity, mut on_monitor, size, migrated) in query.iter_mut() {
+        let home = match gone.iter().find(|(monitor, _)| *monitor == on_monitor.0) {
+            Some((_, name)) => name.clone(),
+            None => continue,
+        };
+        debug!("migrate {entity:?} from monitor {home} to {target:?}");
+        on_monitor.0 = target;
+        let size = size.map(|&Size(size)| size);
+        if let Some(size) = size {
+            commands
+                .entity(entity)
+                .insert(RequestSize(size.clamp_within(&area)));
+        }
+        // entities migrating again still belong to their original monitor
+        if migrated.is_none() {
+            commands.entity(entity).insert(Migrated { home, size });
+        }
+    }
+}
+
+/// Moves [`Migrated`] entities back once their monitor reappears
+fn restore_migrated(
+    query: Query<(Entity, &Migrated)>,
+    monitors: Query<(Entity, &Monitor, &WorkArea), Added<Monitor>>,
+    mut commands: Commands,
+) {
+    for (monitor, Monitor { name, .. }, &WorkArea(area)) in monitors.iter() {
+        for (entity, migrated) in query.iter().filter(|(_, m)| m.home == *name) {
+            debug!("restore {entity:?} to monitor {name}");
+            let mut entity = commands.entity(entity);
+            entity.insert(OnMonitor(monitor)).remove::<Migrated>();
+            if let Some(size) = migrated.size {
+                entity.insert(RequestSize(size.clamp_within(&area)));
+            }
+        }
+    }
+}
@@ -0,0 +1,579 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use log::debug;
+use mwm_xcb::component::{
+    Floating, Fullscreen, IsFocused, IsManaged, IsMapped, NetWmState, WindowType, WmState, WorkArea,
+};
+use mwm_xcb::{XcbStage, XcbSystem};
+use rustc_hash::FxHashSet as HashSet;
+
+use crate::layout::{self, Tile, TileConfig, TileOrder};
+use crate::monitor::{FocusedMonitor, OnMonitor};
+use crate::workspace::{OnWorkspace, Workspace};
+
+/// Tiles workspaces manually, windows get inserted next to the focused one
+/// into a tree of split containers. Builds on the [`layout::LayoutPlugin`]
+pub struct TreePlugin {
+    /// Whether workspaces start out tiled manually
+    pub manual: bool,
+}
+
+impl Default for TreePlugin {
+    fn default() -> Self {
+        Self { manual: true }
+    }
+}
+
+impl Plugin for TreePlugin {
+    fn build(&self, app: &mut App) {
+        let manual = self.manual;
+        app.add_event::<TreeCommand>().add_system_set_to_stage(
+            XcbStage::Policy,
+            SystemSet::new()
+                .with_system(
+                    move |query: Query<Entity, Added<Workspace>>, commands: Commands| {
+                        init_trees(manual, query, commands)
+                    },
+                )
+                .with_system(command_trees)
+                .with_system(remove_leaves.after(command_trees))
+                .with_system(insert_leaves.after(remove_leaves))
+                .with_system(tile_trees.after(insert_leaves).after(XcbSystem::Fullscreen)),
+        );
+    }
+}
+
+/// Root container of a manually tiled workspace
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Tree {
+    pub root: Entity,
+}
+
+/// Direction a container splits its area in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// Children side by side
+    Horizontal,
+    /// Children on top of each other
+    Vertical,
+}
+
+/// Marks container entities, their children are windows or other containers
+#[derive(Component, Debug)]
+pub struct Container {
+    pub split: Split,
+    pub children: Vec<Entity>,
+    /// Share of the area each child gets, adding up to one
+    pub ratios: Vec<f32>,
+}
+
+/// Container a window or container is a child of
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InContainer(pub Entity);
+
+/// Marks windows the next window gets split off from in the given direction
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SplitNext(pub Split);
+
+/// Requests changing the tree of the workspace on the focused monitor
+#[derive(Debug, Clone, Copy)]
+pub enum TreeCommand {
+    /// Switches between manual tiling and the workspace's layouts
+    Toggle,
+    /// Splits the focused window in the given direction for the next window
+    Split(Split),
+    /// Grows the focused window by the given share of its container
+    Resize(f32),
+}
+
+fn init_trees(manual: bool, query: Query<Entity, Added<Workspace>>, mut commands: Commands) {
+    if !manual {
+        return;
+    }
+    for workspace in query.iter() {
+        let root = spawn_root(&mut commands);
+        commands.entity(workspace).insert(Tree { root });
+    }
+}
+
+fn spawn_root(commands: &mut Commands) -> Entity {
+    commands
+        .spawn()
+        .insert(Container {
+            split: Split::Horizontal,
+            children: Vec::new(),
+            ratios: Vec::new(),
+        })
+        .id()
+}
+
+/// Returns the windows in the tree in depth first order along with their
+/// containers
+fn leaves(root: Entity, containers: &Query<&mut Container>) -> Vec<(Entity, Entity)> {
+    let mut leaves = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if let Ok(container) = containers.get(node) {
+            for &child in container.children.iter().rev() {
+                match containers.get(child) {
+                    Ok(_) => stack.push(child),
+                    Err(_) => leaves.push((child, node)),
+                }
+            }
+        }
+    }
+    leaves
+}
+
+/// Applies [`TreeCommand`]s to the workspace on the focused monitor
+fn command_trees(
+    mut events: EventReader<TreeCommand>,
+    workspaces: Query<(Entity, &OnMonitor, Option<&Tree>), With<Workspace>>,
+    focused_monitor: Query<Entity, With<FocusedMonitor>>,
+    focused: Query<Entity, (With<IsFocused>, With<IsManaged>)>,
+    windows: Query<Entity, With<InContainer>>,
+    mut containers: Query<&mut Container>,
+    mut commands: Commands,
+) {
+    let monitor = match focused_monitor.iter().next() {
+        Some(monitor) => OnMonitor(monitor),
+        None => return,
+    };
+    let (workspace, tree) = match workspaces.iter().find(|&(_, &m, _)| m == monitor) {
+        Some((workspace, _, tree)) => (workspace, tree.copied()),
+        None => return,
+    };
+    // only the focused window of this workspace's tree is subject to commands
+    let focused = tree.and_then(|tree| {
+        let focused = focused.iter().next()?;
+        leaves(tree.root, &containers)
+            .into_iter()
+            .find(|&(leaf, _)| leaf == focused)
+    });
+
+    for e in events.iter() {
+        match (*e, tree, focused) {
+            (TreeCommand::Toggle, Some(Tree { root }), _) => {
+                debug!("tile workspace {workspace:?} with layouts");
+                let mut stack = vec![root];
+                while let Some(node) = stack.pop() {
+                    if let Ok(container) = containers.get(node) {
+                        stack.extend(&container.children);
+                        commands.entity(node).despawn();
+                    } else if windows.get(node).is_ok() {
+                        commands.entity(node).remove::<InContainer>();
+                    }
+                }
+                commands.entity(workspace).remove::<Tree>();
+                return;
+            },
+            (TreeCommand::Toggle, None, _) => {
+                debug!("tile workspace {workspace:?} manually");
+                let root = spawn_root(&mut commands);
+                commands.entity(workspace).insert(Tree { root });
+                return;
+            },
+            (TreeCommand::Split(split), Some(_), Some((window, _))) => {
+                commands.entity(window).insert(SplitNext(split));
+            },
+            (TreeCommand::Resize(delta), Some(_), Some((window, parent))) => {
+                let mut container = match containers.get_mut(parent) {
+                    Ok(container) => container,
+                    Err(_) => continue,
+                };
+                let index = match container.children.iter().position(|&c| c == window) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let count = container.ratios.len();
+                if count < 2 {
+                    continue;
+                }
+                // the others give up the space in proportion to their share
+                let old = container.ratios[index];
+                let new = (old + delta).clamp(0.05, 0.95);
+                let scale = (1.0 - new) / (1.0 - old);
+                for ratio in container.ratios.iter_mut() {
+                    *ratio *= scale;
+                }
+                container.ratios[index] = new;
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Whether the window belongs in the tree of its workspace
+fn is_tiled(
+    window_type: &WindowType,
+    state: Option<&WmState>,
+    floating: Option<&Floating>,
+) -> bool {
+    *window_type == WindowType::Normal
+        && matches!(state, Some(WmState::Normal | WmState::Iconic))
+        && floating.is_none()
+}
+
+/// Removes windows from trees which are gone, left their workspace or are no
+/// longer tiled, containers left with a single child get replaced by it
+fn remove_leaves(
+    workspaces: Query<(Entity, &Tree)>,
+    windows: Query<
+        (
+            &OnWorkspace,
+            &WindowType,
+            Option<&WmState>,
+            Option<&Floating>,
+        ),
+        With<IsManaged>,
+    >,
+    mut containers: Query<&mut Container>,
+    mut parents: Query<&mut InContainer>,
+    mut commands: Commands,
+) {
+    for (workspace, &Tree { root }) in workspaces.iter() {
+        let stays = |leaf| match windows.get(leaf) {
+            Ok((&OnWorkspace(w), window_type, state, floating)) => {
+                w == workspace && is_tiled(window_type, state, floating)
+            },
+            Err(_) => false,
+        };
+        // NOTE removals reshape the tree so its leaves are looked at anew
+        while let Some((leaf, parent)) = leaves(root, &containers)
+            .into_iter()
+            .find(|&(leaf, _)| !stays(leaf))
+        {
+            debug!("remove {leaf:?} from tree of {workspace:?}");
+            if windows.get(leaf).is_ok() {
+                commands.entity(leaf).remove::<InContainer>();
+            }
+            remove_child(
+                parent,
+                leaf,
+                root,
+                &mut containers,
+                &mut parents,
+                &mut commands,
+            );
+        }
+    }
+}
+
+fn remove_child(
+    parent: Entity,
+    child: Entity,
+    root: Entity,
+    containers: &mut Query<&mut Container>,
+    parents: &mut Query<&mut InContainer>,
+    commands: &mut Commands,
+) {
+    let mut container = match containers.get_mut(parent) {
+        Ok(container) => container,
+        Err(_) => return,
+    };
+    let index = match container.children.iter().position(|&c| c == child) {
+        Some(index) => index,
+        None => return,
+    };
+    container.children.remove(index);
+    container.ratios.remove(index);
+    let total = container.ratios.iter().sum::<f32>();
+    for ratio in container.ratios.iter_mut() {
+        *ratio /= total;
+    }
+    if parent == root || container.children.len() > 1 {
+        return;
+    }
+
+    // NOTE the root stays even when empty, other containers collapse
+    let remaining = container.children.first().copied();
+    let grandparent = match parents.get(parent) {
+        Ok(&InContainer(grandparent)) => grandparent,
+        Err(_) => return,
+    };
+    commands.entity(parent).despawn();
+    let remaining = match remaining {
+        Some(remaining) => remaining,
+        None => {
+            return remove_child(grandparent, parent, root, containers, parents, commands);
+        },
+    };
+    if let Ok(mut grandparent_container) = containers.get_mut(grandparent) {
+        if let Some(slot) = grandparent_container
+            .children
+            .iter_mut()
+            .find(|c| **c == parent)
+        {
+            *slot = remaining;
+        }
+    }
+    if let Ok(mut in_container) = parents.get_mut(remaining) {
+        in_container.0 = grandparent;
+    }
+}
+
+/// Inserts the tiled windows of a workspace missing from its tree next to its
+/// focused window, splitting it off when it is marked [`SplitNext`]
+fn insert_leaves(
+    workspaces: Query<(Entity, &Tree)>,
+    windows: Query<
+        (
+            Entity,
+            &OnWorkspace,
+            &WindowType,
+            Option<&WmState>,
+            Option<&Floating>,
+            &TileOrder,
+        ),
+        With<IsManaged>,
+    >,
+    focused: Query<(Entity, Option<&SplitNext>), With<IsFocused>>,
+    mut containers: Query<&mut Container>,
+    mut commands: Commands,
+) {
+    for (workspace, &Tree { root }) in workspaces.iter() {
+        let leaves = leaves(root, &containers);
+        let present = leaves.iter().map(|&(leaf, _)| leaf).collect::<HashSet<_>>();
+        let mut missing = windows
+            .iter()
+            .filter(
+                |&(entity, &OnWorkspace(w), window_type, state, floating, _)| {
+                    w == workspace
+                        && is_tiled(window_type, state, floating)
+                        && !present.contains(&entity)
+                },
+            )
+            .map(|(entity, _, _, _, _, &order)| (order, entity))
+            .collect::<Vec<_>>();
+        missing.sort();
+
+        let mut target = focused.iter().find_map(|(entity, split)| {
+            let &(_, parent) = leaves.iter().find(|&&(leaf, _)| leaf == entity)?;
+            Some((entity, parent, split.copied()))
+        });
+        for (_, window) in missing {
+            debug!("insert {window:?} into tree of {workspace:?}");
+            let (sibling, parent, split) = match target {
+                Some(target) => target,
+                None => {
+                    append(root, window, &mut containers, &mut commands);
+                    continue;
+                },
+            };
+            let mut container = match containers.get_mut(parent) {
+                Ok(container) => container,
+                Err(_) => break,
+            };
+            let index = match container.children.iter().position(|&c| c == sibling) {
+                Some(index) => index,
+                None => break,
+            };
+
+            match split {
+                // splitting the only child just turns its container
+                Some(SplitNext(split)) if container.children.len() == 1 => {
+                    container.split = split;
+                    commands.entity(sibling).remove::<SplitNext>();
+                },
+                Some(SplitNext(split)) => {
+                    let wrapper = commands
+                        .spawn()
+                        .insert(Container {
+                            split,
+                            children: vec![sibling, window],
+                            ratios: vec![0.5, 0.5],
+                        })
+                        .insert(InContainer(parent))
+                        .id();
+                    container.children[index] = wrapper;
+                    commands
+                        .entity(sibling)
+                        .insert(InContainer(wrapper))
+                        .remove::<SplitNext>();
+                    commands.entity(window).insert(InContainer(wrapper));
+                    // NOTE the wrapper only exists once commands are applied,
+                    // other windows get inserted next frame
+                    break;
+                },
+                None => {},
+            }
+            let half = container.ratios[index] / 2.0;
+            container.ratios[index] = half;
+            container.children.insert(index + 1, window);
+            container.ratios.insert(index + 1, half);
+            commands.entity(window).insert(InContainer(parent));
+            // the next window follows the one inserted
+            target = Some((window, parent, None));
+        }
+    }
+}
+
+/// Appends the window to the container, shrinking the others to make room
+fn append(
+    parent: Entity,
+    window: Entity,
+    containers: &mut Query<&mut Container>,
+    commands: &mut Commands,
+) {
+    if let Ok(mut container) = containers.get_mut(parent) {
+        let count = container.children.len() as f32;
+        for ratio in container.ratios.iter_mut() {
+            *ratio *= count / (count + 1.0);
+        }
+        container.children.push(window);
+        container.ratios.push(1.0 / (count + 1.0));
+        commands.entity(window).insert(InContainer(parent));
+    }
+}
+
+/// Tiles the mapped windows of every shown, manually tiled workspace into the
+/// work area of its monitor
+fn tile_trees(
+    config: Res<TileConfig>,
+    workspaces: Query<(&Tree, &OnMonitor), With<Workspace>>,
+    monitors: Query<&WorkArea>,
+    containers: Query<&Container>,
+    windows: Query<(Option<&NetWmState>, Option<&Tile>), (With<IsMapped>, Without<Fullscreen>)>,
+    mut commands: Commands,
+) {
+    for (&Tree { root }, &OnMonitor(monitor)) in workspaces.iter() {
+        let area = match monitors.get(monitor) {
+            Ok(&WorkArea(area)) => config.area_gap(area),
+            Err(_) => continue,
+        };
+        let mut stack = vec![(root, area)];
+        while let Some((node, region)) = stack.pop() {
+            if let Ok(container) = containers.get(node) {
+                let regions = match container.split {
+                    Split::Horizontal => region.split_columns_by(&container.ratios),
+                    Split::Vertical => region.split_rows_by(&container.ratios),
+                };
+                stack.extend(container.children.iter().copied().zip(regions));
+            } else if let Ok((state, tile)) = windows.get(node) {
+                let region = config.window_gap(layout::maximize(region, area, state));
+                layout::tile(node, region, tile, &config, &mut commands);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::schedule::SystemStage;
+
+    use super::*;
+
+    struct Fixture {
+        world: World,
+        workspace: Entity,
+        root: Entity,
+        inner: Entity,
+        windows: [Entity; 3],
+    }
+
+    /// Builds a root splitting `a` off a vertical container holding `b` and
+    /// `c`
+    fn tree() -> Fixture {
+        let mut world = World::new();
+        let workspace = world.spawn().id();
+        let root = world.spawn().id();
+        let inner = world.spawn().id();
+        let windows = [(); 3].map(|_| {
+            world
+                .spawn()
+                .insert_bundle((
+                    OnWorkspace(workspace),
+                    WindowType::Normal,
+                    WmState::Normal,
+                    IsManaged,
+                ))
+                .id()
+        });
+        let [a, b, c] = windows;
+        world.entity_mut(workspace).insert(Tree { root });
+        world.entity_mut(root).insert(Container {
+            split: Split::Horizontal,
+            children: vec![a, inner],
+            ratios: vec![0.5, 0.5],
+        });
+        world
+            .entity_mut(inner)
+            .insert(Container {
+                split: Split::Vertical,
+                children: vec![b, c],
+                ratios: vec![0.25, 0.75],
+            })
+            .insert(InContainer(root));
+        world.entity_mut(a).insert(InContainer(root));
+        world.entity_mut(b).insert(InContainer(inner));
+        world.entity_mut(c).insert(InContainer(inner));
+        Fixture { world, workspace, root, inner, windows }
+    }
+
+    fn remove(world: &mut World) {
+        let mut stage = SystemStage::single_threaded().with_system(remove_leaves);
+        stage.run(world);
+    }
+
+    fn children(world: &World, container: Entity) -> (Vec<Entity>, Vec<f32>) {
+        let container = world.get::<Container>(container).unwrap();
+        (container.children.clone(), container.ratios.clone())
+    }
+
+    #[test]
+    fn replaces_containers_left_with_one_child_by_it() {
+        let Fixture {
+            mut world,
+            root,
+            inner,
+            windows: [a, b, c],
+            ..
+        } = tree();
+        world.despawn(b);
+        remove(&mut world);
+
+        assert_eq!(children(&world, root), (vec![a, c], vec![0.5, 0.5]));
+        assert_eq!(world.get::<InContainer>(c), Some(&InContainer(root)));
+        assert!(world.get_entity(inner).is_none());
+    }
+
+    #[test]
+    fn rescales_the_ratios_of_the_remaining_children() {
+        let Fixture {
+            mut world,
+            root,
+            inner,
+            windows: [a, b, c],
+            ..
+        } = tree();
+        world.entity_mut(a).insert(Floating);
+        remove(&mut world);
+
+        assert_eq!(children(&world, root), (vec![inner], vec![1.0]));
+        assert_eq!(children(&world, inner), (vec![b, c], vec![0.25, 0.75]));
+        assert_eq!(world.get::<InContainer>(a), None);
+    }
+
+    #[test]
+    fn keeps_the_root_once_empty() {
+        let Fixture {
+            mut world,
+            workspace,
+            root,
+            inner,
+            windows,
+        } = tree();
+        let other = world.spawn().id();
+        for window in windows {
+            world.entity_mut(window).insert(OnWorkspace(other));
+        }
+        remove(&mut world);
+
+        assert_eq!(children(&world, root), (vec![], vec![]));
+        assert_eq!(world.get::<Tree>(workspace).unwrap().root, root);
+        assert!(world.get_entity(inner).is_none());
+        for window in windows {
+            assert_eq!(world.get::<InContainer>(window), None);
+        }
+    }
+}
@@ -0,0 +1,583 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use log::debug;
+use mwm_xcb::component::{
+    IsFocused, IsManaged, IsMapped, Monitor, NetWmDesktop, NetWmState, Size, WindowType, WmState,
+    WorkArea,
+};
+use mwm_xcb::request::{RequestMap, RequestSize};
+use mwm_xcb::{
+    event as ev, Atom, Desktops, EntityLookup, NetSupported, Region, XcbStage, XcbSystem,
+};
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::monitor::{sorted_monitors, FocusedMonitor, Migrated, OnMonitor};
+
+/// Groups windows into workspaces, every monitor shows one of them
+pub struct WorkspacePlugin {
+    pub names: Vec<String>,
+    pub view: ViewMode,
+}
+
+impl Default for WorkspacePlugin {
+    fn default() -> Self {
+        Self {
+            names: (1..=9).map(|i| i.to_string()).collect(),
+            view: ViewMode::Greedy,
+        }
+    }
+}
+
+impl Plugin for WorkspacePlugin {
+    fn build(&self, app: &mut App) {
+        let names = self.names.clone();
+        app.insert_resource(self.view)
+            .add_event::<ViewWorkspace>()
+            .add_event::<SendToMonitor>()
+            .init_resource::<NetSupported>()
+            .add_startup_system(move |commands: Commands| spawn_workspaces(&names, commands))
+            .add_system(map_windows.after(XcbSystem::DespawnWindows))
+            .add_system(request_desktops.after(XcbSystem::DecodeClientMessages))
+            .add_system_set_to_stage(
+                XcbStage::Policy,
+                SystemSet::new()
+                    .with_system(settle_workspaces)
+                    .with_system(assign_workspaces)
+                    .with_system(follow_workspaces)
+                    .with_system(view_workspaces)
+                    .with_system(send_to_monitors)
+                    .with_system(show_workspaces.after(view_workspaces))
+                    .with_system(publish_workspaces.after(view_workspaces))
+                    .with_system(stick_windows)
+                    .with_system(
+                        publish_window_desktops
+                            .after(assign_workspaces)
+                            .after(stick_windows),
+                    ),
+            );
+        app.world.resource_mut::<NetSupported>().insert(&[
+            Atom::NetNumberOfDesktops,
+            Atom::NetCurrentDesktop,
+            Atom::NetDesktopNames,
+            Atom::NetWmDesktop,
+        ]);
+    }
+}
+
+/// Marks workspace entities, the ones shown are [`OnMonitor`]
+#[derive(Component, Debug)]
+pub struct Workspace {
+    pub name: String,
+    /// Position of the workspace, also its EWMH desktop number
+    pub index: usize,
+}
+
+/// Workspace entity a window is on
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnWorkspace(pub Entity);
+
+/// Marks windows unmapped because their workspace is not shown
+#[derive(Component, Debug)]
+struct HiddenByWorkspace;
+
+/// What viewing a workspace shown on another monitor does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    /// Swaps the workspaces of both monitors
+    Greedy,
+    /// Focuses the other monitor
+    Focus,
+}
+
+/// Requests showing the workspace with the given index on the focused
+/// monitor
+#[derive(Debug, Clone, Copy)]
+pub struct ViewWorkspace(pub usize);
+
+/// Requests moving the focused window to the workspace shown on the monitor
+/// with the given index in [`sorted_monitors`]
+#[derive(Debug, Clone, Copy)]
+pub struct SendToMonitor(pub usize);
+
+fn spawn_workspaces(names: &[String], mut commands: Commands) {
+    for (index, name) in names.iter().enumerate() {
+        commands
+            .spawn()
+            .insert(Workspace { name: name.clone(), index });
+    }
+}
+
+/// Maps windows on request unless their workspace is not shown
+fn map_windows(
+    mut events: EventReader<ev::MapRequest>,
+    lookup: EntityLookup,
+    query: Query<Option<&OnWorkspace>, With<IsManaged>>,
+    shown: Query<(), (With<Workspace>, With<OnMonitor>)>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        let entity = match lookup.get(e.window()) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        match query.get(entity) {
+            Ok(Some(&OnWorkspace(workspace))) if shown.get(workspace).is_err() => {
+                commands.entity(entity).insert(HiddenByWorkspace);
+            },
+            Ok(_) => {
+                commands.entity(entity).insert(RequestMap::Map);
+            },
+            Err(_) => {},
+        }
+    }
+}
+
+/// Turns `_NET_CURRENT_DESKTOP` requests of pagers into [`ViewWorkspace`]
+fn request_desktops(
+    mut events: EventReader<ev::CurrentDesktopRequest>,
+    mut view: EventWriter<ViewWorkspace>,
+) {
+    for e in events.iter() {
+        view.send(ViewWorkspace(e.desktop as usize));
+    }
+}
+
+/// Makes every monitor show exactly one workspace, hidden workspaces are
+/// handed out in order to monitors showing none
+fn settle_workspaces(
+    workspaces: Query<(Entity, &Workspace, Option<&OnMonitor>, Option<&Migrated>)>,
+    monitors: Query<(Entity, &Monitor, &Size)>,
+    added: Query<(), Added<Monitor>>,
+    mut commands: Commands,
+) {
+    let mut sorted = workspaces.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|(_, workspace, _, _)| workspace.index);
+
+    let mut shown = HashMap::<Entity, Vec<_>>::default();
+    let mut hidden = Vec::new();
+    for &(entity, _, on_monitor, migrated) in &sorted {
+        match on_monitor {
+            Some(&OnMonitor(monitor)) => shown
+                .entry(monitor)
+                .or_default()
+                .push((entity, migrated.is_some())),
+            None => hidden.push(entity),
+        }
+    }
+    let mut hidden = hidden.into_iter();
+
+    for monitor in sorted_monitors(&monitors) {
+        match shown.get(&monitor).map(Vec::as_slice) {
+            // NOTE monitors which just showed up might get their workspace
+            // back from before they disappeared
+            None | Some([]) if added.get(monitor).is_ok() => {},
+            None | Some([]) => {
+                if let Some(workspace) = hidden.next() {
+                    debug!("show {workspace:?} on monitor {monitor:?}");
+                    commands.entity(workspace).insert(OnMonitor(monitor));
+                }
+            },
+            Some([_]) => {},
+            // migrated workspaces make way for the one the monitor showed
+            Some(list) => {
+                let keep = list
+                    .iter()
+                    .find(|(_, migrated)| !migrated)
+                    .unwrap_or(&list[0])
+                    .0;
+                for &(workspace, _) in list.iter().filter(|(w, _)| *w != keep) {
+                    commands.entity(workspace).remove::<OnMonitor>();
+                }
+            },
+        }
+    }
+}
+
+/// Puts managed windows on the workspace shown on their monitor. Docks,
+/// desktops and notifications are part of every workspace and stay out of them
+fn assign_workspaces(
+    query: Query<(Entity, &OnMonitor, &WindowType), (With<IsManaged>, Without<OnWorkspace>)>,
+    workspaces: Query<(Entity, &OnMonitor), With<Workspace>>,
+    mut commands: Commands,
+) {
+    for (entity, on_monitor, window_type) in query.iter() {
+        if matches!(
+            window_type,
+            WindowType::Dock | WindowType::Desktop | WindowType::Notification
+        ) {
+            continue;
+        }
+        if let Some((workspace, _)) = workspaces.iter().find(|(_, m)| *m == on_monitor) {
+            commands.entity(entity).insert(OnWorkspace(workspace));
+        }
+    }
+}
+
+/// Keeps windows on the monitor their workspace is shown on
+fn follow_workspaces(
+    mut query: Query<(&OnWorkspace, &mut OnMonitor), Without<Workspace>>,
+    workspaces: Query<&OnMonitor, With<Workspace>>,
+) {
+    for (&OnWorkspace(workspace), mut on_monitor) in query.iter_mut() {
+        if let Ok(&monitor) = workspaces.get(workspace) {
+            if *on_monitor != monitor {
+                *on_monitor = monitor;
+            }
+        }
+    }
+}
+
+/// Shows the requested workspace on the focused monitor, when it is shown on
+/// another monitor already that one either swaps workspaces or gets focused
+fn view_workspaces(
+    mut events: EventReader<ViewWorkspace>,
+    mode: Res<ViewMode>,
+    workspaces: Query<(Entity, &Workspace, Option<&OnMonitor>)>,
+    focused: Query<Entity, With<FocusedMonitor>>,
+    mut commands: Commands,
+) {
+    // NOTE only the last request of a frame counts as commands are deferred
+    let index = match events.iter().last() {
+        Some(&ViewWorkspace(index)) => index,
+        None => return,
+    };
+    let monitor = match focused.iter().next() {
+        Some(monitor) => monitor,
+        None => return,
+    };
+    let (target, workspace, target_monitor) =
+        match workspaces.iter().find(|(_, w, _)| w.index == index) {
+            Some(target) => target,
+            None => return,
+        };
+    let current = workspaces
+        .iter()
+        .find(|&(_, _, m)| m == Some(&OnMonitor(monitor)))
+        .map(|(entity, _, _)| entity);
+
+    match (target_monitor, *mode) {
+        (Some(&OnMonitor(other)), _) if other == monitor => {},
+        (Some(&OnMonitor(other)), ViewMode::Focus) => {
+            debug!(
+                "focus monitor {other:?} showing workspace {}",
+                workspace.name
+            );
+            commands.entity(monitor).remove::<FocusedMonitor>();
+            commands.entity(other).insert(FocusedMonitor);
+        },
+        (Some(&OnMonitor(other)), ViewMode::Greedy) => {
+            debug!("swap workspace {} from monitor {other:?}", workspace.name);
+            commands.entity(target).insert(OnMonitor(monitor));
+            if let Some(current) = current {
+                commands.entity(current).insert(OnMonitor(other));
+            }
+        },
+        (None, _) => {
+            debug!("view workspace {}", workspace.name);
+            commands.entity(target).insert(OnMonitor(monitor));
+            if let Some(current) = current {
+                commands.entity(current).remove::<OnMonitor>();
+            }
+        },
+    }
+}
+
+/// Moves the focused window to the workspace shown on the requested monitor
+/// and fits it into its work area
+fn send_to_monitors(
+    mut events: EventReader<SendToMonitor>,
+    mut windows: Query<(Entity, &Size, &mut OnWorkspace), (With<IsManaged>, With<IsFocused>)>,
+    workspaces: Query<(Entity, &OnMonitor), With<Workspace>>,
+    monitors: Query<(Entity, &Monitor, &Size)>,
+    work_areas: Query<&WorkArea>,
+    mut commands: Commands,
+) {
+    let index = match events.iter().last() {
+        Some(&SendToMonitor(index)) => index,
+        None => return,
+    };
+    let target = match sorted_monitors(&monitors).get(index) {
+        Some(&target) => target,
+        None => return,
+    };
+    let (workspace, area) = match (
+        workspaces.iter().find(|&(_, &m)| m == OnMonitor(target)),
+        work_areas.get(target),
+    ) {
+        (Some((workspace, _)), Ok(&WorkArea(area))) => (workspace, area),
+        _ => return,
+    };
+    let (entity, &Size(size), mut on_workspace) = match windows.iter_mut().next() {
+        Some(window) => window,
+        None => return,
+    };
+    if on_workspace.0 == workspace {
+        return;
+    }
+
+    // keep the window at the same spot relative to its monitor
+    let origin = |monitor| monitors.get(monitor).ok().map(|(_, _, &Size(size))| size);
+    let source = workspaces
+        .get(on_workspace.0)
+        .ok()
+        .and_then(|(_, &OnMonitor(monitor))| origin(monitor));
+    let size = match (source, origin(target)) {
+        (Some(from), Some(to)) => Region {
+            x: size.x - from.x + to.x,
+            y: size.y - from.y + to.y,
+            ..size
+        },
+        _ => size,
+    };
+    debug!("send {entity:?} to monitor {target:?}");
+    on_workspace.0 = workspace;
+    commands
+        .entity(entity)
+        .insert(RequestSize(size.clamp_within(&area)));
+}
+
+/// Makes windows asking for [`NetWmDesktop::ALL`] [`NetWmState::STICKY`], EWMH
+/// treats both alike and the sticky state is what workspaces go by
+fn stick_windows(mut query: Query<(&NetWmDesktop, &mut NetWmState), Added<NetWmDesktop>>) {
+    for (&desktop, mut state) in query.iter_mut() {
+        if desktop == NetWmDesktop::ALL && !state.contains(NetWmState::STICKY) {
+            state.insert(NetWmState::STICKY);
+        }
+    }
+}
+
+/// Unmaps windows of workspaces which are not shown and maps the ones hidden
+/// before once their workspace is shown again, unless their clients withdrew
+/// them meanwhile. [`NetWmState::STICKY`] windows move on to the workspace
+/// shown on their monitor instead
+fn show_workspaces(
+    query: Query<
+        (
+            Entity,
+            &OnWorkspace,
+            Option<&OnMonitor>,
+            Option<&IsMapped>,
+            Option<&HiddenByWorkspace>,
+            Option<&WmState>,
+            Option<&NetWmState>,
+        ),
+        With<IsManaged>,
+    >,
+    workspaces: Query<(Entity, &OnMonitor), With<Workspace>>,
+    mut commands: Commands,
+) {
+    for (entity, &OnWorkspace(workspace), on_monitor, mapped, hidden, state, net_state) in
+        query.iter()
+    {
+        let is_shown = workspaces.get(workspace).is_ok();
+        if !is_shown && is_sticky(net_state) {
+            let current = workspaces
+                .iter()
+                .find(|&(_, m)| Some(m) == on_monitor)
+                .map(|(workspace, _)| workspace);
+            if let Some(current) = current {
+                debug!("move sticky {entity:?} along to {current:?}");
+                commands.entity(entity).insert(OnWorkspace(current));
+                continue;
+            }
+        }
+        match (is_shown, mapped, hidden) {
+            (_, _, Some(_)) if state == Some(&WmState::Withdrawn) => {
+                commands.entity(entity).remove::<HiddenByWorkspace>();
+            },
+            (true, _, Some(_)) => {
+                commands
+                    .entity(entity)
+                    .insert(RequestMap::Map)
+                    .remove::<HiddenByWorkspace>();
+            },
+            (false, Some(_), None) => {
+                commands
+                    .entity(entity)
+                    .insert(RequestMap::Unmap)
+                    .insert(HiddenByWorkspace);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Publishes the workspaces as EWMH desktops, the current one being the one
+/// shown on the focused monitor
+fn publish_workspaces(
+    workspaces: Query<(&Workspace, Option<&OnMonitor>)>,
+    focused: Query<Entity, With<FocusedMonitor>>,
+    mut desktops: ResMut<Desktops>,
+) {
+    let focused = focused.iter().next().map(OnMonitor);
+    let mut sorted = workspaces.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|(workspace, _)| workspace.index);
+    let names = sorted
+        .iter()
+        .map(|(workspace, _)| workspace.name.clone())
+        .collect::<Vec<_>>();
+    let current = sorted
+        .iter()
+        .find(|(_, on_monitor)| focused.is_some() && on_monitor.copied() == focused)
+        .map_or(desktops.current, |(workspace, _)| workspace.index as u32);
+    if desktops.names != names || desktops.current != current {
+        *desktops = Desktops { names, current };
+    }
+}
+
+/// Whether the window shows on every workspace
+fn is_sticky(state: Option<&NetWmState>) -> bool {
+    state.is_some_and(|state| state.contains(NetWmState::STICKY))
+}
+
+/// Keeps the [`NetWmDesktop`] of windows in line with their workspace,
+/// [`NetWmState::STICKY`] windows are on [`NetWmDesktop::ALL`]
+fn publish_window_desktops(
+    query: Query<
+        (
+            Entity,
+            &OnWorkspace,
+            Option<&NetWmState>,
+            Option<&NetWmDesktop>,
+        ),
+        Or<(Changed<OnWorkspace>, Changed<NetWmState>)>,
+    >,
+    workspaces: Query<&Workspace>,
+    mut commands: Commands,
+) {
+    for (entity, &OnWorkspace(workspace), state, current) in query.iter() {
+        let desktop = match workspaces.get(workspace) {
+            _ if is_sticky(state) => NetWmDesktop::ALL,
+            Ok(workspace) => NetWmDesktop(workspace.index as u32),
+            Err(_) => continue,
+        };
+        if current != Some(&desktop) {
+            commands.entity(entity).insert(desktop);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::event::Events;
+    use mwm_xcb::backend::{Backend, PropertyData};
+    use mwm_xcb::fake::FakeBackend;
+    use mwm_xcb::test_support::{self, map_window, run, single_monitor};
+    use xcb::Xid;
+
+    use super::*;
+    use crate::monitor::MonitorPlugin;
+
+    const SCREEN: Region = Region { x: 0, y: 0, w: 1920, h: 1080 };
+
+    fn app(fake: &FakeBackend) -> App {
+        single_monitor(fake, SCREEN);
+        let mut app = test_support::app(fake);
+        app.add_plugin(MonitorPlugin)
+            .add_plugin(WorkspacePlugin::default());
+        run(&mut app, 3);
+        app
+    }
+
+    fn view(app: &mut App, index: usize) {
+        app.world
+            .resource_mut::<Events<ViewWorkspace>>()
+            .send(ViewWorkspace(index));
+        run(app, 3);
+    }
+
+    #[test]
+    fn hides_windows_of_workspaces_not_shown() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(SCREEN, false);
+        map_window(&mut app, &fake, window);
+        assert!(fake.window(window).unwrap().is_mapped);
+
+        view(&mut app, 1);
+        assert!(!fake.window(window).unwrap().is_mapped);
+        view(&mut app, 0);
+        assert!(fake.window(window).unwrap().is_mapped);
+    }
+
+    #[test]
+    fn keeps_windows_withdrawn_while_hidden_unmapped() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(SCREEN, false);
+        let entity = map_window(&mut app, &fake, window);
+        view(&mut app, 1);
+        fake.withdraw_window(window);
+        run(&mut app, 2);
+        assert_eq!(
+            app.world.entity(entity).get::<WmState>(),
+            Some(&WmState::Withdrawn)
+        );
+
+        view(&mut app, 0);
+        assert!(!fake.window(window).unwrap().is_mapped);
+        assert!(!app.world.entity(entity).contains::<HiddenByWorkspace>());
+    }
+
+    #[test]
+    fn keeps_windows_on_all_desktops_shown() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(SCREEN, false);
+        let all = PropertyData::U32(vec![NetWmDesktop::ALL.0]);
+        fake.set_property(window, Atom::NetWmDesktop, all.clone());
+        let entity = map_window(&mut app, &fake, window);
+
+        view(&mut app, 1);
+        assert!(fake.window(window).unwrap().is_mapped);
+        let workspace = app.world.entity(entity).get::<OnWorkspace>().unwrap().0;
+        assert_eq!(
+            app.world
+                .entity(workspace)
+                .get::<Workspace>()
+                .unwrap()
+                .index,
+            1
+        );
+        assert_eq!(fake.property(window, Atom::NetWmDesktop), Some(all));
+    }
+
+    #[test]
+    fn keeps_sticky_windows_shown() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(SCREEN, false);
+        let sticky = fake.atom(Atom::NetWmStateSticky).resource_id();
+        fake.set_property(window, Atom::NetWmState, PropertyData::U32(vec![sticky]));
+        map_window(&mut app, &fake, window);
+
+        view(&mut app, 1);
+        assert!(fake.window(window).unwrap().is_mapped);
+        assert_eq!(
+            fake.property(window, Atom::NetWmDesktop),
+            Some(PropertyData::U32(vec![NetWmDesktop::ALL.0]))
+        );
+    }
+
+    #[test]
+    fn keeps_docks_shown_on_every_workspace() {
+        let fake = FakeBackend::new();
+        let mut app = app(&fake);
+        let window = fake.create_window(Region { h: 30, ..SCREEN }, false);
+        let dock = fake.atom(Atom::NetWindowTypeDock).resource_id();
+        fake.set_property(window, Atom::NetWmWindowType, PropertyData::U32(vec![dock]));
+        fake.set_property(
+            window,
+            Atom::NetWmStrut,
+            PropertyData::U32(vec![0, 0, 30, 0]),
+        );
+        let entity = map_window(&mut app, &fake, window);
+        assert!(!app.world.entity(entity).contains::<OnWorkspace>());
+
+        view(&mut app, 1);
+        assert!(fake.window(window).unwrap().is_mapped);
+        let mut work_areas = app.world.query_filtered::<&WorkArea, With<Monitor>>();
+        let areas = work_areas.iter(&app.world).copied().collect::<Vec<_>>();
+        assert_eq!(areas, [WorkArea(Region { y: 30, h: 1050, ..SCREEN })]);
+    }
+}
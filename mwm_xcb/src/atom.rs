@@ -23,41 +23,62 @@ atoms! {
     Manager                      = "MANAGER",
     UTF8String                   = "UTF8_STRING",
     WmClass                      = "WM_CLASS",
-    // WmDeleteWindow               = "WM_DELETE_WINDOW",
+    WmDeleteWindow               = "WM_DELETE_WINDOW",
     WmProtocols                  = "WM_PROTOCOLS",
-    // WmState                      = "WM_STATE",
+    WmState                      = "WM_STATE",
     WmName                       = "WM_NAME",
-    // WmTakeFocus                  = "WM_TAKE_FOCUS",
+    WmTakeFocus                  = "WM_TAKE_FOCUS",
+    WmHints                      = "WM_HINTS",
+    WmNormalHints                = "WM_NORMAL_HINTS",
+    WmTransientFor               = "WM_TRANSIENT_FOR",
     NetActiveWindow              = "_NET_ACTIVE_WINDOW",
-    // NetClientList                = "_NET_CLIENT_LIST",
-    // NetCurrentDesktop            = "_NET_CURRENT_DESKTOP",
-    // NetDesktopNames              = "_NET_DESKTOP_NAMES",
-    // NetNumberOfDesktops          = "_NET_NUMBER_OF_DESKTOPS",
-    // NetSupported                 = "_NET_SUPPORTED",
-    // NetSupportingWmCheck         = "_NET_SUPPORTING_WM_CHECK",
+    NetClientList                = "_NET_CLIENT_LIST",
+    NetClientListStacking        = "_NET_CLIENT_LIST_STACKING",
+    NetCloseWindow               = "_NET_CLOSE_WINDOW",
+    NetCurrentDesktop            = "_NET_CURRENT_DESKTOP",
+    NetDesktopNames              = "_NET_DESKTOP_NAMES",
+    NetNumberOfDesktops          = "_NET_NUMBER_OF_DESKTOPS",
+    NetMoveresizeWindow          = "_NET_MOVERESIZE_WINDOW",
+    NetSupported                 = "_NET_SUPPORTED",
+    NetSupportingWmCheck         = "_NET_SUPPORTING_WM_CHECK",
     // NetSystemTrayOpcode          = "_NET_SYSTEM_TRAY_OPCODE",
     // NetSystemTrayOrientation     = "_NET_SYSTEM_TRAY_ORIENTATION",
     // NetSystemTrayOrientationHorz = "_NET_SYSTEM_TRAY_ORIENTATION_HORZ",
     // NetSystemTrayS0              = "_NET_SYSTEM_TRAY_S0",
-    // NetWmDesktop                 = "_NET_WM_DESKTOP",
+    NetWmDesktop                 = "_NET_WM_DESKTOP",
     NetWmName                    = "_NET_WM_NAME",
     NetWmState                   = "_NET_WM_STATE",
+    NetWmStateModal              = "_NET_WM_STATE_MODAL",
+    NetWmStateSticky             = "_NET_WM_STATE_STICKY",
+    NetWmStateMaximizedVert      = "_NET_WM_STATE_MAXIMIZED_VERT",
+    NetWmStateMaximizedHorz      = "_NET_WM_STATE_MAXIMIZED_HORZ",
+    NetWmStateShaded             = "_NET_WM_STATE_SHADED",
+    NetWmStateSkipTaskbar        = "_NET_WM_STATE_SKIP_TASKBAR",
+    NetWmStateSkipPager          = "_NET_WM_STATE_SKIP_PAGER",
+    NetWmStateHidden             = "_NET_WM_STATE_HIDDEN",
     NetWmStateFullscreen         = "_NET_WM_STATE_FULLSCREEN",
-    // NetWmWindowType              = "_NET_WM_WINDOW_TYPE",
+    NetWmStateAbove              = "_NET_WM_STATE_ABOVE",
+    NetWmStateBelow              = "_NET_WM_STATE_BELOW",
+    NetWmStateDemandsAttention   = "_NET_WM_STATE_DEMANDS_ATTENTION",
+    NetWmWindowType              = "_NET_WM_WINDOW_TYPE",
+    NetWmStrut                   = "_NET_WM_STRUT",
+    NetWmStrutPartial            = "_NET_WM_STRUT_PARTIAL",
+    NetWorkarea                  = "_NET_WORKAREA",
     // XEmbed                       = "_XEMBED",
     // XEmbedInfo                   = "_XEMBED_INFO",
 
-    // NetWindowTypeDesktop         = "_NET_WM_WINDOW_TYPE_DESKTOP",
-    // NetWindowTypeDock            = "_NET_WM_WINDOW_TYPE_DOCK",
-    // NetWindowTypeToolbar         = "_NET_WM_WINDOW_TYPE_TOOLBAR",
-    // NetWindowTypeMenu            = "_NET_WM_WINDOW_TYPE_MENU",
-    // NetWindowTypeUtility         = "_NET_WM_WINDOW_TYPE_UTILITY",
-    // NetWindowTypeSplash          = "_NET_WM_WINDOW_TYPE_SPLASH",
-    // NetWindowTypeDialog          = "_NET_WM_WINDOW_TYPE_DIALOG",
-    // NetWindowTypeDropdownMenu    = "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
-    // NetWindowTypePopupMenu       = "_NET_WM_WINDOW_TYPE_POPUP_MENU",
-    // NetWindowTypeNotification    = "_NET_WM_WINDOW_TYPE_NOTIFICATION",
-    // NetWindowTypeCombo           = "_NET_WM_WINDOW_TYPE_COMBO",
-    // NetWindowTypeDnd             = "_NET_WM_WINDOW_TYPE_DND",
-    // NetWindowTypeNormal          = "_NET_WM_WINDOW_TYPE_NORMAL",
+    NetWindowTypeDesktop         = "_NET_WM_WINDOW_TYPE_DESKTOP",
+    NetWindowTypeDock            = "_NET_WM_WINDOW_TYPE_DOCK",
+    NetWindowTypeToolbar         = "_NET_WM_WINDOW_TYPE_TOOLBAR",
+    NetWindowTypeMenu            = "_NET_WM_WINDOW_TYPE_MENU",
+    NetWindowTypeUtility         = "_NET_WM_WINDOW_TYPE_UTILITY",
+    NetWindowTypeSplash          = "_NET_WM_WINDOW_TYPE_SPLASH",
+    NetWindowTypeDialog          = "_NET_WM_WINDOW_TYPE_DIALOG",
+    NetWindowTypeDropdownMenu    = "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+    NetWindowTypePopupMenu       = "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+    NetWindowTypeTooltip         = "_NET_WM_WINDOW_TYPE_TOOLTIP",
+    NetWindowTypeNotification    = "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+    NetWindowTypeCombo           = "_NET_WM_WINDOW_TYPE_COMBO",
+    NetWindowTypeDnd             = "_NET_WM_WINDOW_TYPE_DND",
+    NetWindowTypeNormal          = "_NET_WM_WINDOW_TYPE_NORMAL",
 }
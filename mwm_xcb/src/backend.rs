@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::atom::Atom;
+use crate::event::XError;
+use crate::Region;
+
+/// Requests the window manager sends to the X server without expecting a
+/// reply
+#[derive(Debug, Clone)]
+pub enum XRequest {
+    MapWindow {
+        window: xcb::x::Window,
+    },
+    UnmapWindow {
+        window: xcb::x::Window,
+    },
+    ConfigureWindow {
+        window: xcb::x::Window,
+        values: Vec<xcb::x::ConfigWindow>,
+    },
+    ChangeProperty {
+        window: xcb::x::Window,
+        property: xcb::x::Atom,
+        r#type: xcb::x::Atom,
+        data: PropertyData,
+    },
+    DeleteProperty {
+        window: xcb::x::Window,
+        property: xcb::x::Atom,
+    },
+    /// Sends a format 32 `ClientMessage` event to the window's client
+    SendClientMessage {
+        window: xcb::x::Window,
+        r#type: xcb::x::Atom,
+        data: [u32; 5],
+    },
+    /// Sends a synthetic `ConfigureNotify` event telling the window's client
+    /// its geometry
+    SendConfigureNotify {
+        window: xcb::x::Window,
+        region: Region,
+        border: u16,
+    },
+    /// Closes the connection of the client owning the window
+    KillClient {
+        window: xcb::x::Window,
+    },
+    /// Gives the window keyboard focus
+    SetInputFocus {
+        window: xcb::x::Window,
+    },
+    /// Selects which events of the window we get notified about
+    SelectInput {
+        window: xcb::x::Window,
+        event_mask: xcb::x::EventMask,
+    },
+}
+
+/// Property payload, variants correspond to the property format. Format 16 is
+/// not supported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyData {
+    U8(Vec<u8>),
+    U32(Vec<u32>),
+}
+
+/// Window which already existed as a child of the root window by the time we
+/// took over
+#[derive(Debug, Clone)]
+pub struct ExistingWindow {
+    pub window: xcb::x::Window,
+    pub region: Region,
+    pub border: u16,
+    pub override_redirect: bool,
+    pub is_mapped: bool,
+}
+
+/// Monitor as reported by RandR
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// Name of the output driving the monitor, stable across reconfiguration
+    pub name: String,
+    pub region: Region,
+    pub primary: bool,
+    /// Refresh rate in Hz, if known
+    pub refresh_rate: Option<f64>,
+}
+
+/// Everything the ECS systems need from an X server. Implemented by the xcb
+/// connection and by [`crate::fake::FakeBackend`]
+pub trait Backend: Send + Sync + 'static {
+    /// The root window we manage
+    fn root(&self) -> xcb::x::Window;
+
+    /// The window advertised as the EWMH supporting WM check window
+    fn check_window(&self) -> xcb::x::Window;
+
+    /// Interned id of the atom
+    fn atom(&self, atom: Atom) -> xcb::x::Atom;
+
+    /// Queues a request, requests are only guaranteed to be sent after
+    /// [`Backend::flush`]
+    fn send(&self, request: XRequest);
+
+    /// Blocks until all queued requests are sent
+    fn flush(&self);
+
+    /// Blocks until at least one event or error arrives, or until the timeout
+    /// expires, and then returns all the queued ones. Errors are caused by
+    /// earlier requests
+    fn wait_for_events(&self, timeout: Option<Duration>) -> Vec<Result<xcb::Event, XError>>;
+
+    /// Returns all the queued events and errors without blocking
+    fn poll_for_events(&self) -> Vec<Result<xcb::Event, XError>>;
+
+    /// Queries the children of the root window together with their attributes
+    /// and geometry. Windows which disappear while being queried are skipped
+    fn existing_windows(&self) -> Result<Vec<ExistingWindow>>;
+
+    /// Geometry of the root window, spanning all monitors
+    fn screen_region(&self) -> Result<Region, XError>;
+
+    /// Queries the active monitors through RandR, or Xinerama if RandR reports
+    /// none. Mirrored outputs are reported once
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, XError>;
+
+    /// Reads a window's property, `None` if it is not set
+    fn get_property(
+        &self,
+        window: xcb::x::Window,
+        property: xcb::x::Atom,
+    ) -> Result<Option<PropertyData>, XError>;
+}
@@ -0,0 +1,108 @@
+use bevy_ecs::prelude::*;
+use log::{debug, trace};
+
+use crate::atom::Atom;
+use crate::event as ev;
+use crate::window_index::EntityLookup;
+use crate::xconn::XConn;
+
+/// Typed EWMH requests decoded from client messages
+#[derive(Debug)]
+enum Request {
+    ActivateWindow(ev::ActivateWindowRequest),
+    CloseWindow(ev::CloseWindowRequest),
+    WmStateChange(ev::WmStateChangeRequest),
+    MoveResize(ev::MoveResizeRequest),
+    CurrentDesktop(ev::CurrentDesktopRequest),
+}
+
+/// Decodes [`ev::ClientMessage`]s into their typed EWMH request events,
+/// messages about windows we don't know are dropped
+pub fn decode_client_messages(
+    xconn: Res<XConn>,
+    mut ev_client_message: EventReader<ev::ClientMessage>,
+    lookup: EntityLookup,
+    mut ev_activate_window: EventWriter<ev::ActivateWindowRequest>,
+    mut ev_close_window: EventWriter<ev::CloseWindowRequest>,
+    mut ev_wm_state_change: EventWriter<ev::WmStateChangeRequest>,
+    (mut ev_move_resize, mut ev_current_desktop): (
+        EventWriter<ev::MoveResizeRequest>,
+        EventWriter<ev::CurrentDesktopRequest>,
+    ),
+) {
+    for e in ev_client_message.iter() {
+        match decode(&xconn, &lookup, e) {
+            Some(Request::ActivateWindow(request)) => ev_activate_window.send(request),
+            Some(Request::CloseWindow(request)) => ev_close_window.send(request),
+            Some(Request::WmStateChange(request)) => ev_wm_state_change.send(request),
+            Some(Request::MoveResize(request)) => ev_move_resize.send(request),
+            Some(Request::CurrentDesktop(request)) => ev_current_desktop.send(request),
+            None => trace!("ignored client message {e:?}"),
+        }
+    }
+}
+
+fn decode(xconn: &XConn, lookup: &EntityLookup, e: &ev::ClientMessage) -> Option<Request> {
+    let data = match e.data() {
+        xcb::x::ClientMessageData::Data32(data) => data,
+        _ => return None,
+    };
+    let r#type = xconn.lookup_atom(e.r#type())?;
+    let window = e.window();
+    debug!("client message {type:?} for {window:?}: {data:?}");
+
+    // root window messages
+    if r#type == Atom::NetCurrentDesktop {
+        return Some(Request::CurrentDesktop(ev::CurrentDesktopRequest {
+            desktop: data[0],
+            timestamp: data[1],
+        }));
+    }
+
+    let entity = lookup.get(window)?;
+    let request = match r#type {
+        Atom::NetActiveWindow => Request::ActivateWindow(ev::ActivateWindowRequest {
+            entity,
+            window,
+            source: ev::RequestSource::from_u32(data[0]),
+            timestamp: data[1],
+        }),
+        Atom::NetCloseWindow => Request::CloseWindow(ev::CloseWindowRequest {
+            entity,
+            window,
+            source: ev::RequestSource::from_u32(data[1]),
+            timestamp: data[0],
+        }),
+        Atom::NetWmState => Request::WmStateChange(ev::WmStateChangeRequest {
+            entity,
+            window,
+            action: match data[0] {
+                0 => ev::WmStateAction::Remove,
+                1 => ev::WmStateAction::Add,
+                2 => ev::WmStateAction::Toggle,
+                _ => return None,
+            },
+            states: data[1..3]
+                .iter()
+                .filter_map(|&atom| xconn.lookup_atom_id(atom))
+                .collect(),
+            source: ev::RequestSource::from_u32(data[3]),
+        }),
+        Atom::NetMoveresizeWindow => {
+            let flags = data[0];
+            let has = |bit: u32| flags & (1 << bit) != 0;
+            Request::MoveResize(ev::MoveResizeRequest {
+                entity,
+                window,
+                gravity: (flags & 0xff) as u8,
+                x: has(8).then_some(data[1] as i32),
+                y: has(9).then_some(data[2] as i32),
+                w: has(10).then_some(data[3]),
+                h: has(11).then_some(data[4]),
+                source: ev::RequestSource::from_u32((flags >> 12) & 0xf),
+            })
+        },
+        _ => return None,
+    };
+    Some(request)
+}
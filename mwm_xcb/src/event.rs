@@ -1,5 +1,8 @@
+use bevy_ecs::entity::Entity;
 use xcb::{randr, x};
 
+use crate::Atom;
+
 macro_rules! event_wrappers {
     ( $( $w:ident $e:path ),+ $(,)? ) => {
         $(
@@ -53,3 +56,120 @@ event_wrappers! {
     ScreenChangeNotify  randr::ScreenChangeNotifyEvent,
     Notify              randr::NotifyEvent,
 }
+
+/// Protocol error reported by the X server, e.g. a `BadWindow` caused by a
+/// request racing with the window getting destroyed
+#[derive(Debug, Clone)]
+pub struct XError {
+    pub kind: XErrorKind,
+    /// Name of the request which caused the error, if known
+    pub request: Option<&'static str>,
+    /// The offending resource id or value, zero for errors which don't carry
+    /// one
+    pub resource_id: u32,
+    pub major_opcode: u8,
+    pub minor_opcode: u16,
+    pub sequence: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XErrorKind {
+    Request,
+    Value,
+    Window,
+    Pixmap,
+    Atom,
+    Cursor,
+    Font,
+    Match,
+    Drawable,
+    Access,
+    Alloc,
+    Colormap,
+    GContext,
+    IdChoice,
+    Name,
+    Length,
+    Implementation,
+    /// Error defined by one of the protocol extensions
+    Extension,
+}
+
+/// Who sent an EWMH request, pagers and taskbars act on behalf of the user
+/// and are usually trusted more than applications
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestSource {
+    /// Clients predating the source indication
+    Legacy,
+    Application,
+    Pager,
+}
+
+impl RequestSource {
+    pub(crate) fn from_u32(source: u32) -> Self {
+        match source {
+            1 => RequestSource::Application,
+            2 => RequestSource::Pager,
+            _ => RequestSource::Legacy,
+        }
+    }
+}
+
+/// `_NET_ACTIVE_WINDOW` client message asking for the window to be activated
+#[derive(Debug, Clone)]
+pub struct ActivateWindowRequest {
+    pub entity: Entity,
+    pub window: x::Window,
+    pub source: RequestSource,
+    pub timestamp: u32,
+}
+
+/// `_NET_CLOSE_WINDOW` client message asking for the window to be closed
+#[derive(Debug, Clone)]
+pub struct CloseWindowRequest {
+    pub entity: Entity,
+    pub window: x::Window,
+    pub source: RequestSource,
+    pub timestamp: u32,
+}
+
+/// What a [`WmStateChangeRequest`] does with its states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WmStateAction {
+    Remove,
+    Add,
+    Toggle,
+}
+
+/// `_NET_WM_STATE` client message asking for one or two states of the window
+/// to change, states we don't know are left out
+#[derive(Debug, Clone)]
+pub struct WmStateChangeRequest {
+    pub entity: Entity,
+    pub window: x::Window,
+    pub action: WmStateAction,
+    pub states: Vec<Atom>,
+    pub source: RequestSource,
+}
+
+/// `_NET_MOVERESIZE_WINDOW` client message, fields the client left out are
+/// `None`
+#[derive(Debug, Clone)]
+pub struct MoveResizeRequest {
+    pub entity: Entity,
+    pub window: x::Window,
+    /// Window gravity, 0 means the one from `WM_NORMAL_HINTS`
+    pub gravity: u8,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    pub source: RequestSource,
+}
+
+/// `_NET_CURRENT_DESKTOP` client message asking to switch desktops
+#[derive(Debug, Clone)]
+pub struct CurrentDesktopRequest {
+    pub desktop: u32,
+    pub timestamp: u32,
+}
@@ -0,0 +1,312 @@
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::EntityCommands;
+use log::debug;
+use xcb::Xid;
+
+use crate::atom::Atom;
+use crate::backend::{PropertyData, XRequest};
+use crate::component::*;
+use crate::event as ev;
+use crate::property::WindowProperty;
+use crate::xconn::XConn;
+
+/// Name advertised on the check window
+const WM_NAME: &str = "mwm";
+
+/// EWMH hints the window manager supports, published on the root window as
+/// `_NET_SUPPORTED`. Features add their atoms as they get enabled
+#[derive(Debug)]
+pub struct NetSupported(Vec<Atom>);
+
+impl Default for NetSupported {
+    fn default() -> Self {
+        NetSupported(vec![
+            Atom::NetSupported,
+            Atom::NetSupportingWmCheck,
+            Atom::NetWmName,
+        ])
+    }
+}
+
+impl NetSupported {
+    pub fn insert(&mut self, atoms: &[Atom]) {
+        for &atom in atoms {
+            if !self.0.contains(&atom) {
+                self.0.push(atom);
+            }
+        }
+    }
+
+    pub fn contains(&self, atom: Atom) -> bool {
+        self.0.contains(&atom)
+    }
+}
+
+/// Marks us as an EWMH compliant window manager by pointing
+/// `_NET_SUPPORTING_WM_CHECK` of both the root and the check window at the
+/// check window, which also gets our `_NET_WM_NAME`
+pub fn publish_wm_check(xconn: Res<XConn>) {
+    let check_win = xconn.check_window();
+    for window in [xconn.root(), check_win] {
+        xconn.send(XRequest::ChangeProperty {
+            window,
+            property: xconn.atom(Atom::NetSupportingWmCheck),
+            r#type: xcb::x::ATOM_WINDOW,
+            data: PropertyData::U32(vec![check_win.resource_id()]),
+        });
+    }
+    xconn.send(XRequest::ChangeProperty {
+        window: check_win,
+        property: xconn.atom(Atom::NetWmName),
+        r#type: xconn.atom(Atom::UTF8String),
+        data: PropertyData::U8(WM_NAME.as_bytes().to_vec()),
+    });
+}
+
+/// Publishes `_NET_SUPPORTED` whenever features get added to [`NetSupported`]
+pub fn publish_net_supported(xconn: Res<XConn>, supported: Res<NetSupported>) {
+    if !supported.is_changed() {
+        return;
+    }
+    debug!("supported hints {supported:?}");
+    xconn.send(XRequest::ChangeProperty {
+        window: xconn.root(),
+        property: xconn.atom(Atom::NetSupported),
+        r#type: xcb::x::ATOM_ATOM,
+        data: PropertyData::U32(
+            supported
+                .0
+                .iter()
+                .map(|&atom| xconn.atom(atom).resource_id())
+                .collect(),
+        ),
+    });
+}
+
+/// Managed windows in the orders pagers and taskbars want them, published as
+/// `_NET_CLIENT_LIST` and `_NET_CLIENT_LIST_STACKING`
+#[derive(Default, Debug)]
+pub struct ClientList {
+    // managed windows in the order they were first mapped
+    clients: Vec<(Entity, xcb::x::Window)>,
+    // all children of the root window from bottom to top
+    stacking: Vec<xcb::x::Window>,
+}
+
+impl ClientList {
+    /// Managed window entities in the order they were first mapped
+    pub fn clients(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.clients.iter().map(|&(entity, _)| entity)
+    }
+
+    /// Managed windows from bottom to top
+    pub fn stacking(&self) -> impl Iterator<Item = xcb::x::Window> + '_ {
+        self.stacking
+            .iter()
+            .copied()
+            .filter(|&window| self.clients.iter().any(|&(_, w)| w == window))
+    }
+
+    /// Adds a window on top of the stack, for windows already existing at
+    /// startup in the order they are stacked
+    pub(crate) fn push_stacking(&mut self, window: xcb::x::Window) {
+        self.stacking.push(window);
+    }
+
+    /// Returns the stacking order with the window moved directly above
+    /// `sibling`, to the bottom if `sibling` is `None`
+    fn restacked(
+        &self,
+        window: xcb::x::Window,
+        sibling: Option<xcb::x::Window>,
+    ) -> Vec<xcb::x::Window> {
+        let mut stacking = self.stacking.clone();
+        stacking.retain(|&w| w != window);
+        let index = match sibling {
+            None => 0,
+            Some(sibling) => stacking
+                .iter()
+                .position(|&w| w == sibling)
+                .map_or(stacking.len(), |i| i + 1),
+        };
+        stacking.insert(index, window);
+        stacking
+    }
+}
+
+/// Keeps the [`ClientList`] up to date with windows getting managed, withdrawn
+/// and destroyed
+pub fn update_client_list(
+    mut client_list: ResMut<ClientList>,
+    mapped: Query<(Entity, &Window), (With<IsManaged>, Added<IsMapped>)>,
+    withdrawn: Query<(Entity, &WmState), Changed<WmState>>,
+    removed: RemovedComponents<Window>,
+) {
+    for (entity, &Window(window)) in mapped.iter() {
+        if !client_list.clients.iter().any(|&(e, _)| e == entity) {
+            client_list.clients.push((entity, window));
+        }
+    }
+    let withdrawn = withdrawn
+        .iter()
+        .filter(|&(_, &state)| state == WmState::Withdrawn)
+        .map(|(entity, _)| entity);
+    for entity in withdrawn.chain(removed.iter()) {
+        if client_list.clients.iter().any(|&(e, _)| e == entity) {
+            client_list.clients.retain(|&(e, _)| e != entity);
+        }
+    }
+}
+
+/// Keeps the [`ClientList`] stacking order up to date with windows getting
+/// created, destroyed and restacked
+pub fn update_client_stacking(
+    mut client_list: ResMut<ClientList>,
+    mut ev_create_notify: EventReader<ev::CreateNotify>,
+    mut ev_destroy_notify: EventReader<ev::DestroyNotify>,
+    mut ev_configure_notify: EventReader<ev::ConfigureNotify>,
+    mut ev_circulate_notify: EventReader<ev::CirculateNotify>,
+) {
+    for e in ev_create_notify.iter() {
+        if !client_list.stacking.contains(&e.window()) {
+            client_list.stacking.push(e.window());
+        }
+    }
+    for e in ev_destroy_notify.iter() {
+        client_list.stacking.retain(|&w| w != e.window());
+    }
+    for e in ev_configure_notify.iter() {
+        let sibling = Some(e.above_sibling()).filter(|sibling| !sibling.is_none());
+        let stacking = client_list.restacked(e.window(), sibling);
+        if stacking != client_list.stacking {
+            client_list.stacking = stacking;
+        }
+    }
+    for e in ev_circulate_notify.iter() {
+        let sibling = match e.place() {
+            xcb::x::Place::OnTop => client_list.stacking.last().copied(),
+            xcb::x::Place::OnBottom => None,
+        };
+        let stacking = client_list.restacked(e.window(), sibling);
+        if stacking != client_list.stacking {
+            client_list.stacking = stacking;
+        }
+    }
+}
+
+/// Publishes `_NET_CLIENT_LIST` and `_NET_CLIENT_LIST_STACKING` at most once
+/// per frame when the [`ClientList`] changed
+pub fn publish_client_list(xconn: Res<XConn>, client_list: Res<ClientList>) {
+    if !client_list.is_changed() {
+        return;
+    }
+    let clients = client_list
+        .clients
+        .iter()
+        .map(|(_, window)| window.resource_id())
+        .collect();
+    let stacking = client_list
+        .stacking()
+        .map(|window| window.resource_id())
+        .collect();
+    for (atom, windows) in [
+        (Atom::NetClientList, clients),
+        (Atom::NetClientListStacking, stacking),
+    ] {
+        xconn.send(XRequest::ChangeProperty {
+            window: xconn.root(),
+            property: xconn.atom(atom),
+            r#type: xcb::x::ATOM_WINDOW,
+            data: PropertyData::U32(windows),
+        });
+    }
+}
+
+/// Virtual desktops published as `_NET_NUMBER_OF_DESKTOPS`,
+/// `_NET_DESKTOP_NAMES` and `_NET_CURRENT_DESKTOP`, kept up to date by the
+/// window manager
+#[derive(Default, Debug)]
+pub struct Desktops {
+    pub names: Vec<String>,
+    pub current: u32,
+}
+
+/// Publishes the [`Desktops`] whenever they changed
+pub fn publish_desktops(xconn: Res<XConn>, desktops: Res<Desktops>) {
+    if !desktops.is_changed() {
+        return;
+    }
+    debug!("desktops {desktops:?}");
+    let names = desktops
+        .names
+        .iter()
+        .flat_map(|name| name.bytes().chain([0]))
+        .collect();
+    for (property, r#type, data) in [
+        (
+            Atom::NetNumberOfDesktops,
+            xcb::x::ATOM_CARDINAL,
+            PropertyData::U32(vec![desktops.names.len() as u32]),
+        ),
+        (
+            Atom::NetCurrentDesktop,
+            xcb::x::ATOM_CARDINAL,
+            PropertyData::U32(vec![desktops.current]),
+        ),
+        (
+            Atom::NetDesktopNames,
+            xconn.atom(Atom::UTF8String),
+            PropertyData::U8(names),
+        ),
+    ] {
+        xconn.send(XRequest::ChangeProperty {
+            window: xconn.root(),
+            property: xconn.atom(property),
+            r#type,
+            data,
+        });
+    }
+}
+
+/// Clients may ask for a desktop, e.g. [`NetWmDesktop::ALL`], before mapping
+/// their windows. Windows keep the desktop they are on when the property is
+/// gone, it's ours to publish from then on
+impl WindowProperty for NetWmDesktop {
+    const ATOMS: &'static [Atom] = &[Atom::NetWmDesktop];
+
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError> {
+        match xconn.get_property(window, xconn.atom(Atom::NetWmDesktop))? {
+            Some(PropertyData::U32(desktop)) if !desktop.is_empty() => {
+                Ok(Some(NetWmDesktop(desktop[0])))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn remove(_entity: &mut EntityCommands) {}
+}
+
+/// Publishes the [`NetWmDesktop`] of windows as their `_NET_WM_DESKTOP`
+pub fn publish_wm_desktops(
+    xconn: Res<XConn>,
+    query: Query<(&Window, &NetWmDesktop), Changed<NetWmDesktop>>,
+) {
+    for (&Window(window), &NetWmDesktop(desktop)) in query.iter() {
+        xconn.send(XRequest::ChangeProperty {
+            window,
+            property: xconn.atom(Atom::NetWmDesktop),
+            r#type: xcb::x::ATOM_CARDINAL,
+            data: PropertyData::U32(vec![desktop]),
+        });
+    }
+}
+
+/// Points `_NET_ACTIVE_WINDOW` at the window, or at none
+pub(crate) fn set_active_window(xconn: &XConn, window: Option<xcb::x::Window>) {
+    xconn.send(XRequest::ChangeProperty {
+        window: xconn.root(),
+        property: xconn.atom(Atom::NetActiveWindow),
+        r#type: xcb::x::ATOM_WINDOW,
+        data: PropertyData::U32(vec![window.map_or(0, |window| window.resource_id())]),
+    });
+}
@@ -0,0 +1,575 @@
+//! In-memory stand-in for the X server, for running the ECS systems without
+//! Xephyr
+//!
+//! [`FakeBackend`] records every request it receives, simulates the events a
+//! real X server would send back for them (`MapNotify` for `MapWindow`,
+//! `ConfigureNotify` for `ConfigureWindow`, ...) and lets clients be simulated
+//! by injecting synthetic events. Requests for windows it doesn't know about
+//! fail with a `BadWindow` error, as they would on a real server.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::Result;
+use rustc_hash::FxHashMap as HashMap;
+use xcb::{randr, x, BaseEvent, Raw, Xid, XidNew};
+
+use crate::atom::Atom;
+use crate::backend::{Backend, ExistingWindow, MonitorInfo, PropertyData, XRequest};
+use crate::event::{XError, XErrorKind};
+use crate::Region;
+
+/// Window as tracked by the [`FakeBackend`]
+#[derive(Debug, Clone)]
+pub struct FakeWindow {
+    pub window: x::Window,
+    pub region: Region,
+    pub border: u16,
+    pub override_redirect: bool,
+    pub is_mapped: bool,
+    pub event_mask: x::EventMask,
+}
+
+/// Cheaply clonable handle to an in-memory X server, keep a clone around to
+/// inspect the server after handing it to [`crate::XConn::new`]
+#[derive(Clone)]
+pub struct FakeBackend(Arc<Mutex<FakeServer>>);
+
+// NOTE xcb::Event is not Send because of its unknown event variant
+enum FakeEvent {
+    X(x::Event),
+    RandR(randr::Event),
+}
+
+struct FakeServer {
+    next_id: u32,
+
+    root: x::Window,
+    screen: Region,
+    check_win: x::Window,
+    atoms: HashMap<Atom, x::Atom>,
+
+    // children of the root window, in stacking order from bottom to top
+    windows: Vec<FakeWindow>,
+    focus: x::Window,
+    properties: HashMap<(x::Window, x::Atom), PropertyData>,
+
+    monitors: Vec<MonitorInfo>,
+
+    events: VecDeque<Result<FakeEvent, XError>>,
+    requests: Vec<XRequest>,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        let mut server = FakeServer {
+            next_id: 0x0040_0000,
+            root: x::Window::none(),
+            screen: Region { x: 0, y: 0, w: 1920, h: 1080 },
+            check_win: x::Window::none(),
+            atoms: HashMap::default(),
+            windows: Vec::new(),
+            focus: x::Window::none(),
+            properties: HashMap::default(),
+            monitors: Vec::new(),
+            events: VecDeque::new(),
+            requests: Vec::new(),
+        };
+        server.root = server.generate_id();
+        server.check_win = server.generate_id();
+        for &atom in Atom::ALL {
+            let id = server.generate_id();
+            server.atoms.insert(atom, id);
+        }
+        FakeBackend(Arc::new(Mutex::new(server)))
+    }
+
+    /// Simulates a client creating a window, queues a `CreateNotify`
+    pub fn create_window(&self, region: Region, override_redirect: bool) -> x::Window {
+        let mut server = self.server();
+        let window = server.add_window(region, override_redirect, false);
+        let root = server.root;
+        server.push(x::Event::CreateNotify(x::CreateNotifyEvent::new(
+            root,
+            window,
+            region.x as i16,
+            region.y as i16,
+            region.w as u16,
+            region.h as u16,
+            0,
+            override_redirect,
+        )));
+        window
+    }
+
+    /// Adds a window which exists before the window manager starts, no event is
+    /// queued
+    pub fn add_existing_window(
+        &self,
+        region: Region,
+        override_redirect: bool,
+        is_mapped: bool,
+    ) -> x::Window {
+        self.server()
+            .add_window(region, override_redirect, is_mapped)
+    }
+
+    /// Simulates a client destroying its window, queues a `DestroyNotify`
+    pub fn destroy_window(&self, window: x::Window) {
+        self.server().destroy(window);
+    }
+
+    /// Simulates a client asking for its window to be mapped, queues a
+    /// `MapRequest`
+    pub fn request_map(&self, window: x::Window) {
+        let mut server = self.server();
+        let root = server.root;
+        server.push(x::Event::MapRequest(x::MapRequestEvent::new(root, window)));
+    }
+
+    /// Simulates a client withdrawing its window as ICCCM 4.1.4 asks, unmapping
+    /// it if mapped and queueing a synthetic `UnmapNotify`
+    pub fn withdraw_window(&self, window: x::Window) {
+        let mut server = self.server();
+        let root = server.root;
+        if let Some(w) = server.window_mut(window).filter(|w| w.is_mapped) {
+            w.is_mapped = false;
+            server.push(x::Event::UnmapNotify(x::UnmapNotifyEvent::new(
+                root, window, false,
+            )));
+        }
+        server.push(x::Event::UnmapNotify(synthetic_unmap_notify(root, window)));
+    }
+
+    /// Simulates a client asking for its window to be configured, queues a
+    /// `ConfigureRequest`
+    pub fn request_configure(&self, window: x::Window, region: Region, border: u16) {
+        let mut server = self.server();
+        let root = server.root;
+        server.push(x::Event::ConfigureRequest(x::ConfigureRequestEvent::new(
+            x::StackMode::Above,
+            root,
+            window,
+            x::Window::none(),
+            region.x as i16,
+            region.y as i16,
+            region.w as u16,
+            region.h as u16,
+            border,
+            x::ConfigWindowMask::X
+                | x::ConfigWindowMask::Y
+                | x::ConfigWindowMask::WIDTH
+                | x::ConfigWindowMask::HEIGHT
+                | x::ConfigWindowMask::BORDER_WIDTH,
+        )));
+    }
+
+    /// Simulates a client setting a property on its window, queues a
+    /// `PropertyNotify` if the window manager selected property changes
+    pub fn set_property(&self, window: x::Window, atom: Atom, data: PropertyData) {
+        let mut server = self.server();
+        let property = server.atoms[&atom];
+        server.properties.insert((window, property), data);
+        server.push_property_notify(window, property, x::Property::NewValue);
+    }
+
+    /// Simulates a client sending a 32 bit client message about its window to
+    /// the root window, queues the `ClientMessage`
+    pub fn send_client_message(&self, window: x::Window, r#type: Atom, data: [u32; 5]) {
+        let mut server = self.server();
+        let r#type = server.atoms[&r#type];
+        server.push(x::Event::ClientMessage(x::ClientMessageEvent::new(
+            window,
+            r#type,
+            x::ClientMessageData::Data32(data),
+        )));
+    }
+
+    /// Queues an arbitrary event
+    pub fn push_event(&self, event: x::Event) {
+        self.server().events.push_back(Ok(FakeEvent::X(event)));
+    }
+
+    /// Queues an arbitrary error
+    pub fn push_error(&self, error: XError) {
+        self.server().events.push_back(Err(error));
+    }
+
+    /// All the requests received so far
+    pub fn requests(&self) -> Vec<XRequest> {
+        self.server().requests.clone()
+    }
+
+    /// Returns and forgets the requests received so far
+    pub fn take_requests(&self) -> Vec<XRequest> {
+        std::mem::take(&mut self.server().requests)
+    }
+
+    /// Current state of a window
+    pub fn window(&self, window: x::Window) -> Option<FakeWindow> {
+        self.server()
+            .windows
+            .iter()
+            .find(|w| w.window == window)
+            .cloned()
+    }
+
+    /// Resizes the root window, 1920x1080 by default
+    pub fn set_screen_region(&self, region: Region) {
+        self.server().screen = region;
+    }
+
+    /// Simulates a RandR reconfiguration, queues a `ScreenChangeNotify`.
+    /// Without monitors RandR reports none, as if it was unavailable
+    pub fn set_monitors(&self, monitors: Vec<MonitorInfo>) {
+        let mut server = self.server();
+        server.monitors = monitors;
+        let root = server.root;
+        let Region { w, h, .. } = server.screen;
+        server
+            .events
+            .push_back(Ok(FakeEvent::RandR(randr::Event::ScreenChangeNotify(
+                randr::ScreenChangeNotifyEvent::new(
+                    0,
+                    randr::Rotation::ROTATE_0,
+                    x::CURRENT_TIME,
+                    x::CURRENT_TIME,
+                    root,
+                    root,
+                    0,
+                    xcb::render::SubPixel::Unknown,
+                    w as u16,
+                    h as u16,
+                    0,
+                    0,
+                ),
+            ))));
+    }
+
+    /// Window with the input focus
+    pub fn focus(&self) -> x::Window {
+        self.server().focus
+    }
+
+    /// Windows in stacking order from bottom to top
+    pub fn stacking_order(&self) -> Vec<x::Window> {
+        self.server().windows.iter().map(|w| w.window).collect()
+    }
+
+    /// Current value of a window's property
+    pub fn property(&self, window: x::Window, atom: Atom) -> Option<PropertyData> {
+        let server = self.server();
+        let property = server.atoms[&atom];
+        server.properties.get(&(window, property)).cloned()
+    }
+
+    fn server(&self) -> MutexGuard<'_, FakeServer> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl FakeServer {
+    fn generate_id<T: XidNew>(&mut self) -> T {
+        self.next_id += 1;
+        // SAFETY ids are unique within the fake server
+        unsafe { T::new(self.next_id) }
+    }
+
+    fn add_window(
+        &mut self,
+        region: Region,
+        override_redirect: bool,
+        is_mapped: bool,
+    ) -> x::Window {
+        let window = self.generate_id();
+        self.windows.push(FakeWindow {
+            window,
+            region,
+            border: 0,
+            override_redirect,
+            is_mapped,
+            event_mask: x::EventMask::NO_EVENT,
+        });
+        window
+    }
+
+    fn push(&mut self, event: x::Event) {
+        self.events.push_back(Ok(FakeEvent::X(event)));
+    }
+
+    // NOTE the root window's event mask is not tracked, its property changes
+    // are always reported
+    fn push_property_notify(&mut self, window: x::Window, property: x::Atom, state: x::Property) {
+        let selected = self
+            .windows
+            .iter()
+            .find(|w| w.window == window)
+            .map_or(true, |w| {
+                w.event_mask.contains(x::EventMask::PROPERTY_CHANGE)
+            });
+        if selected {
+            self.push(x::Event::PropertyNotify(x::PropertyNotifyEvent::new(
+                window,
+                property,
+                x::CURRENT_TIME,
+                state,
+            )));
+        }
+    }
+
+    fn window_mut(&mut self, window: x::Window) -> Option<&mut FakeWindow> {
+        self.windows.iter_mut().find(|w| w.window == window)
+    }
+
+    fn exists(&self, window: x::Window) -> bool {
+        window == self.root
+            || window == self.check_win
+            || self.windows.iter().any(|w| w.window == window)
+    }
+
+    fn process(&mut self, request: &XRequest) {
+        let (name, window) = match *request {
+            XRequest::MapWindow { window } => ("MapWindow", window),
+            XRequest::UnmapWindow { window } => ("UnmapWindow", window),
+            XRequest::ConfigureWindow { window, .. } => ("ConfigureWindow", window),
+            XRequest::ChangeProperty { window, .. } => ("ChangeProperty", window),
+            XRequest::DeleteProperty { window, .. } => ("DeleteProperty", window),
+            XRequest::SendClientMessage { window, .. } => ("SendEvent", window),
+            XRequest::SendConfigureNotify { window, .. } => ("SendEvent", window),
+            XRequest::KillClient { window } => ("KillClient", window),
+            XRequest::SetInputFocus { window } => ("SetInputFocus", window),
+            XRequest::SelectInput { window, .. } => ("ChangeWindowAttributes", window),
+        };
+        if !self.exists(window) {
+            self.events.push_back(Err(bad_window(name, window)));
+            return;
+        }
+
+        let root = self.root;
+        match request {
+            &XRequest::MapWindow { window } => {
+                if let Some(w) = self.window_mut(window).filter(|w| !w.is_mapped) {
+                    w.is_mapped = true;
+                    let override_redirect = w.override_redirect;
+                    self.push(x::Event::MapNotify(x::MapNotifyEvent::new(
+                        root,
+                        window,
+                        override_redirect,
+                    )));
+                }
+            },
+            &XRequest::UnmapWindow { window } => {
+                if let Some(w) = self.window_mut(window).filter(|w| w.is_mapped) {
+                    w.is_mapped = false;
+                    self.push(x::Event::UnmapNotify(x::UnmapNotifyEvent::new(
+                        root, window, false,
+                    )));
+                }
+            },
+            XRequest::ConfigureWindow { window, values } => {
+                let position = match self.windows.iter().position(|w| w.window == *window) {
+                    Some(position) => position,
+                    None => return,
+                };
+                let mut w = self.windows.remove(position);
+                let mut index = position;
+                for value in values {
+                    match *value {
+                        x::ConfigWindow::X(x) => w.region.x = x,
+                        x::ConfigWindow::Y(y) => w.region.y = y,
+                        x::ConfigWindow::Width(width) => w.region.w = width,
+                        x::ConfigWindow::Height(height) => w.region.h = height,
+                        x::ConfigWindow::BorderWidth(border) => w.border = border as u16,
+                        x::ConfigWindow::StackMode(x::StackMode::Above) => {
+                            index = self.windows.len()
+                        },
+                        x::ConfigWindow::StackMode(x::StackMode::Below) => index = 0,
+                        _ => {},
+                    }
+                }
+                let above_sibling = index
+                    .checked_sub(1)
+                    .map_or(x::Window::none(), |i| self.windows[i].window);
+                self.push(x::Event::ConfigureNotify(x::ConfigureNotifyEvent::new(
+                    root,
+                    w.window,
+                    above_sibling,
+                    w.region.x as i16,
+                    w.region.y as i16,
+                    w.region.w as u16,
+                    w.region.h as u16,
+                    w.border,
+                    w.override_redirect,
+                )));
+                self.windows.insert(index, w);
+            },
+            XRequest::ChangeProperty { window, property, data, .. } => {
+                self.properties.insert((*window, *property), data.clone());
+                self.push_property_notify(*window, *property, x::Property::NewValue);
+            },
+            XRequest::DeleteProperty { window, property } => {
+                if self.properties.remove(&(*window, *property)).is_some() {
+                    self.push_property_notify(*window, *property, x::Property::Delete);
+                }
+            },
+            XRequest::SendClientMessage { .. } | XRequest::SendConfigureNotify { .. } => {
+                // clients are simulated by the user of the fake, nothing to
+                // deliver to
+            },
+            &XRequest::KillClient { window } => {
+                if let Some(w) = self.window_mut(window).filter(|w| w.is_mapped) {
+                    w.is_mapped = false;
+                    self.push(x::Event::UnmapNotify(x::UnmapNotifyEvent::new(
+                        root, window, false,
+                    )));
+                }
+                self.destroy(window);
+            },
+            &XRequest::SetInputFocus { window } => {
+                let previous = std::mem::replace(&mut self.focus, window);
+                if previous != window {
+                    if !previous.is_none() {
+                        self.push(x::Event::FocusOut(x::FocusOutEvent::new(
+                            x::NotifyDetail::Nonlinear,
+                            previous,
+                            x::NotifyMode::Normal,
+                        )));
+                    }
+                    self.push(x::Event::FocusIn(x::FocusInEvent::new(
+                        x::NotifyDetail::Nonlinear,
+                        window,
+                        x::NotifyMode::Normal,
+                    )));
+                }
+            },
+            &XRequest::SelectInput { window, event_mask } => {
+                if let Some(w) = self.window_mut(window) {
+                    w.event_mask = event_mask;
+                }
+            },
+        }
+    }
+
+    fn destroy(&mut self, window: x::Window) {
+        if self.focus == window {
+            self.focus = x::Window::none();
+        }
+        self.windows.retain(|w| w.window != window);
+        self.properties.retain(|&(w, _), _| w != window);
+        let root = self.root;
+        self.push(x::Event::DestroyNotify(x::DestroyNotifyEvent::new(
+            root, window,
+        )));
+    }
+}
+
+/// Builds an `UnmapNotify` as sent with `SendEvent`, the high bit of its
+/// response type set
+fn synthetic_unmap_notify(event: x::Window, window: x::Window) -> x::UnmapNotifyEvent {
+    let mut wire = [0u8; 32];
+    wire[0] = x::UnmapNotifyEvent::NUMBER as u8 | 0x80;
+    wire[4..8].copy_from_slice(&event.resource_id().to_ne_bytes());
+    wire[8..12].copy_from_slice(&window.resource_id().to_ne_bytes());
+    // SAFETY the event takes ownership of the buffer and frees it on drop,
+    // so it comes from malloc and holds the whole 32 byte event
+    unsafe {
+        let raw = libc::malloc(wire.len()).cast::<u8>();
+        assert!(!raw.is_null(), "out of memory");
+        raw.copy_from_nonoverlapping(wire.as_ptr(), wire.len());
+        x::UnmapNotifyEvent::from_raw(raw.cast())
+    }
+}
+
+impl Backend for FakeBackend {
+    fn root(&self) -> x::Window {
+        self.server().root
+    }
+
+    fn check_window(&self) -> x::Window {
+        self.server().check_win
+    }
+
+    fn atom(&self, atom: Atom) -> x::Atom {
+        self.server().atoms[&atom]
+    }
+
+    fn send(&self, request: XRequest) {
+        let mut server = self.server();
+        server.process(&request);
+        server.requests.push(request);
+    }
+
+    fn flush(&self) {}
+
+    /// Never blocks, returns an empty buffer when no events are queued
+    fn wait_for_events(&self, _timeout: Option<Duration>) -> Vec<Result<xcb::Event, XError>> {
+        self.poll_for_events()
+    }
+
+    fn poll_for_events(&self) -> Vec<Result<xcb::Event, XError>> {
+        self.server()
+            .events
+            .drain(..)
+            .map(|ev| {
+                ev.map(|ev| match ev {
+                    FakeEvent::X(ev) => xcb::Event::X(ev),
+                    FakeEvent::RandR(ev) => xcb::Event::RandR(ev),
+                })
+            })
+            .collect()
+    }
+
+    fn existing_windows(&self) -> Result<Vec<ExistingWindow>> {
+        let windows = self
+            .server()
+            .windows
+            .iter()
+            .map(|w| ExistingWindow {
+                window: w.window,
+                region: w.region,
+                border: w.border,
+                override_redirect: w.override_redirect,
+                is_mapped: w.is_mapped,
+            })
+            .collect();
+        Ok(windows)
+    }
+
+    fn screen_region(&self) -> Result<Region, XError> {
+        Ok(self.server().screen)
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, XError> {
+        Ok(self.server().monitors.clone())
+    }
+
+    fn get_property(
+        &self,
+        window: x::Window,
+        property: x::Atom,
+    ) -> Result<Option<PropertyData>, XError> {
+        let server = self.server();
+        if !server.exists(window) {
+            return Err(bad_window("GetProperty", window));
+        }
+        Ok(server.properties.get(&(window, property)).cloned())
+    }
+}
+
+fn bad_window(request: &'static str, window: x::Window) -> XError {
+    XError {
+        kind: XErrorKind::Window,
+        request: Some(request),
+        resource_id: window.resource_id(),
+        major_opcode: 0,
+        minor_opcode: 0,
+        sequence: 0,
+    }
+}
@@ -0,0 +1,27 @@
+use bevy_ecs::prelude::*;
+use log::debug;
+
+use crate::backend::XRequest;
+use crate::component::*;
+use crate::xconn::XConn;
+
+/// Raises mapped [`Floating`] windows above the others whenever a window gets
+/// mapped or starts floating, [`Fullscreen`] windows stay above them
+pub fn restack_floating(
+    xconn: Res<XConn>,
+    mapped: Query<(), (Added<IsMapped>, With<IsManaged>)>,
+    floated: Query<(), Added<Floating>>,
+    floating: Query<&Window, (With<Floating>, With<IsMapped>, Without<Fullscreen>)>,
+    fullscreen: Query<&Window, (With<Fullscreen>, With<IsMapped>)>,
+) {
+    if mapped.is_empty() && floated.is_empty() {
+        return;
+    }
+    for &Window(window) in floating.iter().chain(fullscreen.iter()) {
+        debug!("raising window {window:?}");
+        xconn.send(XRequest::ConfigureWindow {
+            window,
+            values: vec![xcb::x::ConfigWindow::StackMode(xcb::x::StackMode::Above)],
+        });
+    }
+}
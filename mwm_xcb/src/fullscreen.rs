@@ -0,0 +1,61 @@
+use bevy_ecs::prelude::*;
+use log::debug;
+
+use crate::backend::XRequest;
+use crate::component::*;
+use crate::monitor::monitor_for;
+use crate::request::*;
+use crate::xconn::XConn;
+use crate::Region;
+
+/// Geometry of a [`Fullscreen`] window from before it went fullscreen
+#[derive(Component, Debug)]
+pub(crate) struct Unfullscreened {
+    size: Region,
+    border: u16,
+}
+
+/// Sizes windows which just went [`Fullscreen`] to their monitor, drops their
+/// border and raises them
+pub fn enter_fullscreen(
+    xconn: Res<XConn>,
+    mut query: Query<(Entity, &Window, &Size, &Border, &mut NetWmState), Added<Fullscreen>>,
+    monitors: Query<(Entity, &Monitor, &Size)>,
+    mut commands: Commands,
+) {
+    for (entity, &Window(window), &Size(size), &Border(border), mut state) in query.iter_mut() {
+        let monitor = match monitor_for(size, &monitors).map(|monitor| monitors.get(monitor)) {
+            Some(Ok((_, _, &Size(monitor)))) => monitor,
+            _ => continue,
+        };
+        debug!("fullscreen window {window:?} on {monitor:?}");
+        commands.entity(entity).insert_bundle((
+            Unfullscreened { size, border },
+            RequestSize(monitor),
+            RequestBorder(0),
+        ));
+        xconn.send(XRequest::ConfigureWindow {
+            window,
+            values: vec![xcb::x::ConfigWindow::StackMode(xcb::x::StackMode::Above)],
+        });
+        state.insert(NetWmState::FULLSCREEN);
+    }
+}
+
+/// Restores the geometry of windows which are no longer [`Fullscreen`]
+pub fn exit_fullscreen(
+    removed: RemovedComponents<Fullscreen>,
+    mut query: Query<(&Window, &Unfullscreened, &mut NetWmState), Without<Fullscreen>>,
+    mut commands: Commands,
+) {
+    for entity in removed.iter() {
+        if let Ok((&Window(window), restore, mut state)) = query.get_mut(entity) {
+            debug!("restore window {window:?} to {restore:?}");
+            commands
+                .entity(entity)
+                .insert_bundle((RequestSize(restore.size), RequestBorder(restore.border)))
+                .remove::<Unfullscreened>();
+            state.remove(NetWmState::FULLSCREEN);
+        }
+    }
+}
@@ -1,21 +1,45 @@
 #![allow(clippy::type_complexity)]
 
 mod atom;
+pub mod backend;
+mod client_message;
 mod diagnostic;
 pub mod event;
+mod ewmh;
+#[cfg(any(test, feature = "fake"))] pub mod fake;
+mod floating;
+mod fullscreen;
+mod monitor;
+mod net_wm_state;
 mod plugin;
+mod property;
+mod size_hints;
+mod strut;
+#[cfg(any(test, feature = "fake"))] pub mod test_support;
+#[cfg(test)] mod tests;
+mod window_index;
+mod window_type;
+mod wm_class;
 mod xcb_event_systems;
 mod xcb_request_systems;
 mod xconn;
 
-pub use plugin::XcbPlugin;
+pub use atom::Atom;
+pub use ewmh::{ClientList, Desktops, NetSupported};
+pub use monitor::monitor_for;
+pub use plugin::{XcbPlugin, XcbStage, XcbSystem};
+pub use window_index::{EntityLookup, WindowIndex};
+pub use xcb_event_systems::WakeUp;
+pub use xconn::XConn;
 
 pub mod component {
     use std::fmt::{self, Debug};
+    use std::time::Instant;
 
     use bevy_ecs::component::Component;
 
-    use crate::Region;
+    use crate::event::WmStateAction;
+    use crate::{Atom, Region};
 
     /// Wrapper for [`xcb::x::Window`] implementing the `Component` trait
     #[derive(Component, Clone, Copy)]
@@ -62,6 +86,216 @@ pub mod component {
 
     #[derive(Component, Debug)]
     pub struct Border(pub u16);
+
+    /// ICCCM state of managed windows, mirrored into their `WM_STATE`
+    /// property
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum WmState {
+        Withdrawn = 0,
+        Normal = 1,
+        Iconic = 3,
+    }
+
+    /// Marks windows whose clients were asked to close them, the clients get
+    /// killed if the windows are still around at the contained deadline
+    #[derive(Component, Debug)]
+    pub struct Closing(pub Instant);
+
+    /// Marks managed windows which are not tiled, they keep the geometry their
+    /// clients ask for and stay above other windows
+    #[derive(Component, Debug)]
+    pub struct Floating;
+
+    /// Size constraints of a window from its `WM_NORMAL_HINTS`, applied to
+    /// the sizes requested for it
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct SizeHints {
+        pub min: Option<(u32, u32)>,
+        pub max: Option<(u32, u32)>,
+        pub base: Option<(u32, u32)>,
+        pub increment: Option<(u32, u32)>,
+        /// Minimum and maximum aspect ratio as width and height pairs
+        pub aspect: Option<((u32, u32), (u32, u32))>,
+        /// Which part of the requested region the window sticks to when it
+        /// ends up smaller, as an X gravity
+        pub gravity: Option<u8>,
+    }
+
+    bitflags::bitflags! {
+        /// Constraints of the [`SizeHints`] not applied to a managed window,
+        /// e.g. tiled windows filling their tile while keeping their minimum
+        /// size
+        #[derive(Component, Default)]
+        pub struct IgnoreSizeHints: u8 {
+            const MIN = 1 << 0;
+            const MAX = 1 << 1;
+            const INCREMENT = 1 << 2;
+            const ASPECT = 1 << 3;
+        }
+    }
+
+    /// Instance and class name of a window from its `WM_CLASS`
+    #[derive(Component, Debug, Clone, PartialEq, Eq)]
+    pub struct WmClass {
+        pub instance: String,
+        pub class: String,
+    }
+
+    /// Marks windows covering their whole monitor without a border, removing
+    /// it restores their previous geometry
+    #[derive(Component, Debug)]
+    pub struct Fullscreen;
+
+    /// Functional type of a window from its `_NET_WM_WINDOW_TYPE`, windows
+    /// without one are [`WindowType::Dialog`] if they are transient for
+    /// another window and [`WindowType::Normal`] otherwise
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum WindowType {
+        Desktop,
+        Dock,
+        Toolbar,
+        Menu,
+        Utility,
+        Splash,
+        Dialog,
+        DropdownMenu,
+        PopupMenu,
+        Tooltip,
+        Notification,
+        Combo,
+        Dnd,
+        Normal,
+    }
+
+    impl WindowType {
+        /// The window type named by the atom
+        pub fn from_atom(atom: Atom) -> Option<Self> {
+            let window_type = match atom {
+                Atom::NetWindowTypeDesktop => WindowType::Desktop,
+                Atom::NetWindowTypeDock => WindowType::Dock,
+                Atom::NetWindowTypeToolbar => WindowType::Toolbar,
+                Atom::NetWindowTypeMenu => WindowType::Menu,
+                Atom::NetWindowTypeUtility => WindowType::Utility,
+                Atom::NetWindowTypeSplash => WindowType::Splash,
+                Atom::NetWindowTypeDialog => WindowType::Dialog,
+                Atom::NetWindowTypeDropdownMenu => WindowType::DropdownMenu,
+                Atom::NetWindowTypePopupMenu => WindowType::PopupMenu,
+                Atom::NetWindowTypeTooltip => WindowType::Tooltip,
+                Atom::NetWindowTypeNotification => WindowType::Notification,
+                Atom::NetWindowTypeCombo => WindowType::Combo,
+                Atom::NetWindowTypeDnd => WindowType::Dnd,
+                Atom::NetWindowTypeNormal => WindowType::Normal,
+                _ => return None,
+            };
+            Some(window_type)
+        }
+    }
+
+    /// Desktop a window is on, published as its `_NET_WM_DESKTOP`.
+    /// [`NetWmDesktop::ALL`] puts it on all of them
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct NetWmDesktop(pub u32);
+
+    impl NetWmDesktop {
+        pub const ALL: NetWmDesktop = NetWmDesktop(0xffff_ffff);
+    }
+
+    /// Marks monitor entities, their [`Size`] is their part of the screen
+    #[derive(Component, Debug)]
+    pub struct Monitor {
+        /// Name of the output, stable across reconfiguration
+        pub name: String,
+        pub primary: bool,
+        /// Refresh rate in Hz, if known
+        pub refresh_rate: Option<f64>,
+    }
+
+    /// Part of a monitor's [`Size`] not reserved by docks, where windows get
+    /// laid out
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WorkArea(pub Region);
+
+    /// Screen edges reserved by a dock through `_NET_WM_STRUT_PARTIAL` or
+    /// `_NET_WM_STRUT`. Widths count from the edges of the whole screen, the
+    /// ranges are inclusive coordinates along the edge
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Strut {
+        pub left: u32,
+        pub right: u32,
+        pub top: u32,
+        pub bottom: u32,
+        pub left_range: (u32, u32),
+        pub right_range: (u32, u32),
+        pub top_range: (u32, u32),
+        pub bottom_range: (u32, u32),
+    }
+
+    bitflags::bitflags! {
+        /// EWMH states of managed windows, mirrored into their `_NET_WM_STATE`
+        /// property. [`NetWmState::FULLSCREEN`] follows the [`Fullscreen`]
+        /// marker and [`NetWmState::HIDDEN`] follows iconification, layouts
+        /// can react to the rest through `Changed<NetWmState>`
+        #[derive(Component, Default)]
+        pub struct NetWmState: u32 {
+            const MODAL = 1 << 0;
+            const STICKY = 1 << 1;
+            const MAXIMIZED_VERT = 1 << 2;
+            const MAXIMIZED_HORZ = 1 << 3;
+            const SHADED = 1 << 4;
+            const SKIP_TASKBAR = 1 << 5;
+            const SKIP_PAGER = 1 << 6;
+            const HIDDEN = 1 << 7;
+            const FULLSCREEN = 1 << 8;
+            const ABOVE = 1 << 9;
+            const BELOW = 1 << 10;
+            const DEMANDS_ATTENTION = 1 << 11;
+        }
+    }
+
+    impl NetWmState {
+        const ATOMS: [(NetWmState, Atom); 12] = [
+            (NetWmState::MODAL, Atom::NetWmStateModal),
+            (NetWmState::STICKY, Atom::NetWmStateSticky),
+            (NetWmState::MAXIMIZED_VERT, Atom::NetWmStateMaximizedVert),
+            (NetWmState::MAXIMIZED_HORZ, Atom::NetWmStateMaximizedHorz),
+            (NetWmState::SHADED, Atom::NetWmStateShaded),
+            (NetWmState::SKIP_TASKBAR, Atom::NetWmStateSkipTaskbar),
+            (NetWmState::SKIP_PAGER, Atom::NetWmStateSkipPager),
+            (NetWmState::HIDDEN, Atom::NetWmStateHidden),
+            (NetWmState::FULLSCREEN, Atom::NetWmStateFullscreen),
+            (NetWmState::ABOVE, Atom::NetWmStateAbove),
+            (NetWmState::BELOW, Atom::NetWmStateBelow),
+            (
+                NetWmState::DEMANDS_ATTENTION,
+                Atom::NetWmStateDemandsAttention,
+            ),
+        ];
+
+        /// States named by the atoms, unknown atoms are skipped
+        pub fn from_atoms(atoms: &[Atom]) -> Self {
+            NetWmState::ATOMS
+                .iter()
+                .filter(|(_, atom)| atoms.contains(atom))
+                .fold(NetWmState::empty(), |states, &(state, _)| states | state)
+        }
+
+        /// Atoms of the contained states
+        pub fn atoms(self) -> impl Iterator<Item = Atom> {
+            NetWmState::ATOMS
+                .into_iter()
+                .filter(move |&(state, _)| self.contains(state))
+                .map(|(_, atom)| atom)
+        }
+
+        /// Applies the action of a `_NET_WM_STATE` client message
+        pub fn apply(&mut self, action: WmStateAction, states: NetWmState) {
+            match action {
+                WmStateAction::Remove => self.remove(states),
+                WmStateAction::Add => self.insert(states),
+                WmStateAction::Toggle => self.toggle(states),
+            }
+        }
+    }
 }
 
 /// Requests are either components or events which are generated in the `Update`
@@ -85,6 +319,22 @@ pub mod request {
     /// Requests the marked window entity to have a border set
     #[derive(Component, Debug)]
     pub struct RequestBorder(pub u16);
+
+    /// Requests the marked window entity to be closed, via `WM_DELETE_WINDOW`
+    /// if the client supports it or by killing the client otherwise
+    #[derive(Component, Debug)]
+    pub struct RequestClose;
+
+    /// Requests a synthetic `ConfigureNotify` with the marked window entity's
+    /// actual geometry, unless it gets resized anyway. ICCCM 4.1.5 requires it
+    /// when a client's configure request isn't honored
+    #[derive(Component, Debug)]
+    pub struct RequestConfigureNotify;
+
+    /// Requests the marked window entity to get keyboard focus, applied once
+    /// the window is mapped
+    #[derive(Component, Debug)]
+    pub struct RequestFocus;
 }
 
 
@@ -107,6 +357,82 @@ impl Region {
         self.w == 0 || self.h == 0
     }
 
+    pub fn contains(&self, Point { x, y }: Point) -> bool {
+        i64::from(x) >= i64::from(self.x)
+            && i64::from(x) < i64::from(self.x) + i64::from(self.w)
+            && i64::from(y) >= i64::from(self.y)
+            && i64::from(y) < i64::from(self.y) + i64::from(self.h)
+    }
+
+    /// Moves the region inside the area, shrinking it if it doesn't fit
+    pub fn clamp_within(&self, area: &Region) -> Region {
+        let w = self.w.min(area.w);
+        let h = self.h.min(area.h);
+        let max_x = i64::from(area.x) + i64::from(area.w - w);
+        let max_y = i64::from(area.y) + i64::from(area.h - h);
+        Region {
+            x: i64::from(self.x).clamp(i64::from(area.x), max_x) as i32,
+            y: i64::from(self.y).clamp(i64::from(area.y), max_y) as i32,
+            w,
+            h,
+        }
+    }
+
+    /// Shrinks the region by `before` on its top and left and by `after` on
+    /// its bottom and right
+    pub fn shrink(&self, before: u32, after: u32) -> Region {
+        Region {
+            x: self.x.saturating_add(before.min(self.w) as i32),
+            y: self.y.saturating_add(before.min(self.h) as i32),
+            w: self.w.saturating_sub(before + after),
+            h: self.h.saturating_sub(before + after),
+        }
+    }
+
+    /// Splits the region into `n` rows of about equal height
+    pub fn split_rows(&self, n: u32) -> Vec<Region> {
+        (0..n)
+            .map(|i| {
+                let top = self.h * i / n;
+                let bottom = self.h * (i + 1) / n;
+                Region {
+                    y: self.y + top as i32,
+                    h: bottom - top,
+                    ..*self
+                }
+            })
+            .collect()
+    }
+
+    /// Splits the region into `n` columns of about equal width
+    pub fn split_columns(&self, n: u32) -> Vec<Region> {
+        (0..n)
+            .map(|i| {
+                let left = self.w * i / n;
+                let right = self.w * (i + 1) / n;
+                Region {
+                    x: self.x + left as i32,
+                    w: right - left,
+                    ..*self
+                }
+            })
+            .collect()
+    }
+
+    /// Splits the region into rows with heights in proportion to the ratios
+    pub fn split_rows_by(&self, ratios: &[f32]) -> Vec<Region> {
+        split_by(self.h, ratios)
+            .map(|(top, h)| Region { y: self.y + top as i32, h, ..*self })
+            .collect()
+    }
+
+    /// Splits the region into columns with widths in proportion to the ratios
+    pub fn split_columns_by(&self, ratios: &[f32]) -> Vec<Region> {
+        split_by(self.w, ratios)
+            .map(|(left, w)| Region { x: self.x + left as i32, w, ..*self })
+            .collect()
+    }
+
     pub fn relative_center(&self) -> Point {
         let Region { w, h, .. } = *self;
         Point {
@@ -115,3 +441,17 @@ impl Region {
         }
     }
 }
+
+/// Splits a length into offsets and lengths in proportion to the ratios
+fn split_by(length: u32, ratios: &[f32]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let total = ratios.iter().sum::<f32>();
+    let offset = move |sum: f32| match total > 0.0 {
+        true => (length as f32 * sum / total).round() as u32,
+        false => 0,
+    };
+    ratios.iter().scan(0.0, move |sum, ratio| {
+        let start = offset(*sum);
+        *sum += ratio;
+        Some((start, offset(*sum).saturating_sub(start)))
+    })
+}
@@ -0,0 +1,116 @@
+use bevy_ecs::prelude::*;
+use log::{debug, error, warn};
+
+use crate::backend::MonitorInfo;
+use crate::component::*;
+use crate::xconn::XConn;
+use crate::{event as ev, Point, Region};
+
+/// Spawns the monitor entities at startup
+pub fn discover_monitors(
+    xconn: Res<XConn>,
+    query: Query<(Entity, &mut Monitor, &mut Size)>,
+    commands: Commands,
+) {
+    sync_monitors(&xconn, query, commands);
+}
+
+/// Reacts to RandR [`ev::ScreenChangeNotify`] and [`ev::Notify`] events and
+/// spawns, updates and despawns monitor entities to match the outputs
+pub fn update_monitors(
+    xconn: Res<XConn>,
+    mut ev_screen_change_notify: EventReader<ev::ScreenChangeNotify>,
+    mut ev_notify: EventReader<ev::Notify>,
+    query: Query<(Entity, &mut Monitor, &mut Size)>,
+    commands: Commands,
+) {
+    // NOTE both iterators have to run through to mark the events as read
+    let screen_changed = ev_screen_change_notify.iter().count() > 0;
+    let outputs_changed = ev_notify.iter().count() > 0;
+    if screen_changed || outputs_changed {
+        sync_monitors(&xconn, query, commands);
+    }
+}
+
+/// Matches monitor entities to the monitors the X server reports by name. Falls
+/// back to a single monitor covering the root window when neither RandR nor
+/// Xinerama report any
+fn sync_monitors(
+    xconn: &XConn,
+    mut query: Query<(Entity, &mut Monitor, &mut Size)>,
+    mut commands: Commands,
+) {
+    let mut monitors = match xconn.monitors() {
+        Ok(monitors) => monitors,
+        Err(err) => {
+            warn!("failed to query monitors: {err:?}");
+            Vec::new()
+        },
+    };
+    if monitors.is_empty() {
+        match xconn.screen_region() {
+            Ok(region) => monitors.push(MonitorInfo {
+                name: String::from("screen"),
+                region,
+                primary: true,
+                refresh_rate: None,
+            }),
+            Err(err) => {
+                error!("failed to query the screen size: {err:?}");
+                return;
+            },
+        }
+    }
+
+    for (entity, mut monitor, mut size) in query.iter_mut() {
+        let info = match monitors.iter().position(|info| info.name == monitor.name) {
+            Some(position) => monitors.remove(position),
+            None => {
+                debug!("despawn monitor {monitor:?}");
+                commands.entity(entity).despawn();
+                continue;
+            },
+        };
+        if size.0 != info.region {
+            debug!(
+                "monitor {name} moved to {region:?}",
+                name = info.name,
+                region = info.region
+            );
+            size.0 = info.region;
+        }
+        if monitor.primary != info.primary || monitor.refresh_rate != info.refresh_rate {
+            monitor.primary = info.primary;
+            monitor.refresh_rate = info.refresh_rate;
+        }
+    }
+
+    for info in monitors {
+        debug!("spawn monitor {info:?}");
+        commands.spawn().insert_bundle((
+            Monitor {
+                name: info.name,
+                primary: info.primary,
+                refresh_rate: info.refresh_rate,
+            },
+            Size(info.region),
+            WorkArea(info.region),
+        ));
+    }
+}
+
+/// Picks the monitor the center of the region is on, the primary one if it is
+/// on none
+pub fn monitor_for(region: Region, monitors: &Query<(Entity, &Monitor, &Size)>) -> Option<Entity> {
+    let center = region.relative_center();
+    let center = Point {
+        x: region.x + center.x,
+        y: region.y + center.y,
+    };
+    monitors
+        .iter()
+        .find(|(_, _, Size(monitor))| monitor.contains(center))
+        .or_else(|| monitors.iter().find(|(_, monitor, _)| monitor.primary))
+        .or_else(|| monitors.iter().next())
+        .map(|(entity, _, _)| entity)
+}
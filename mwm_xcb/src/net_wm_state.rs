@@ -0,0 +1,82 @@
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::EntityCommands;
+use xcb::Xid;
+
+use crate::atom::Atom;
+use crate::backend::{PropertyData, XRequest};
+use crate::component::*;
+use crate::event as ev;
+use crate::property::WindowProperty;
+use crate::xconn::XConn;
+
+/// Unknown states are skipped, a requested fullscreen state becomes a
+/// [`Fullscreen`] marker. Clients only set `_NET_WM_STATE` before mapping their
+/// windows, so it's never read again once we publish it
+impl WindowProperty for NetWmState {
+    const ATOMS: &'static [Atom] = &[Atom::NetWmState];
+
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError> {
+        let atoms = match xconn.get_property(window, xconn.atom(Atom::NetWmState))? {
+            Some(PropertyData::U32(atoms)) => atoms,
+            _ => return Ok(Some(NetWmState::empty())),
+        };
+        let atoms = atoms
+            .into_iter()
+            .filter_map(|atom| xconn.lookup_atom_id(atom))
+            .collect::<Vec<_>>();
+        Ok(Some(NetWmState::from_atoms(&atoms)))
+    }
+
+    fn insert(mut self, entity: &mut EntityCommands) {
+        if self.contains(NetWmState::FULLSCREEN) {
+            self.remove(NetWmState::FULLSCREEN);
+            entity.insert(Fullscreen);
+        }
+        entity.insert(self);
+    }
+}
+
+/// Raises windows kept [`NetWmState::ABOVE`] and lowers ones kept
+/// [`NetWmState::BELOW`] the others, maximized windows get raised above the
+/// neighbours they cover
+pub fn restack_net_wm_state(
+    xconn: Res<XConn>,
+    query: Query<(&Window, &NetWmState), (With<IsManaged>, Changed<NetWmState>)>,
+) {
+    for (&Window(window), state) in query.iter() {
+        let stack_mode = if state.contains(NetWmState::ABOVE) {
+            xcb::x::StackMode::Above
+        } else if state.contains(NetWmState::BELOW) {
+            xcb::x::StackMode::Below
+        } else if state.intersects(NetWmState::MAXIMIZED_VERT | NetWmState::MAXIMIZED_HORZ) {
+            xcb::x::StackMode::Above
+        } else {
+            continue;
+        };
+        xconn.send(XRequest::ConfigureWindow {
+            window,
+            values: vec![xcb::x::ConfigWindow::StackMode(stack_mode)],
+        });
+    }
+}
+
+/// Mirrors [`NetWmState`] into the `_NET_WM_STATE` property of managed
+/// windows
+pub fn publish_net_wm_state(
+    xconn: Res<XConn>,
+    query: Query<(&Window, &NetWmState), (With<IsManaged>, Changed<NetWmState>)>,
+) {
+    for (&Window(window), &state) in query.iter() {
+        xconn.send(XRequest::ChangeProperty {
+            window,
+            property: xconn.atom(Atom::NetWmState),
+            r#type: xcb::x::ATOM_ATOM,
+            data: PropertyData::U32(
+                state
+                    .atoms()
+                    .map(|atom| xconn.atom(atom).resource_id())
+                    .collect(),
+            ),
+        });
+    }
+}
@@ -1,16 +1,74 @@
+use std::time::Duration;
+
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use log::debug;
+use bevy_ecs::system::EntityCommands;
+use log::{debug, error, warn};
 
+use crate::client_message::*;
 use crate::component::*;
+use crate::ewmh::*;
+use crate::floating::*;
+use crate::fullscreen::*;
+use crate::monitor::*;
+use crate::net_wm_state::*;
+use crate::property::{self, read_properties, update_properties};
 use crate::request::*;
+use crate::size_hints::*;
+use crate::strut::*;
 use crate::xcb_event_systems::*;
 use crate::xcb_request_systems::*;
 use crate::xconn::XConn;
-use crate::{diagnostic, event as ev, Region};
+use crate::{diagnostic, event as ev, Atom, EntityLookup, Region, WakeUp, WindowIndex};
+
+pub struct XcbPlugin {
+    /// How long clients get to close their windows after being asked to via
+    /// `WM_DELETE_WINDOW` before they are killed, 5 seconds by default. `None`
+    /// never kills them
+    pub close_timeout: Option<Duration>,
+}
+
+impl Default for XcbPlugin {
+    fn default() -> Self {
+        Self {
+            close_timeout: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
+/// Configured [`XcbPlugin::close_timeout`]
+pub(crate) struct CloseTimeout(pub Option<Duration>);
+
+/// Labels for ordering against the window lifecycle systems
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum XcbSystem {
+    /// Despawns destroyed windows and drops them from the [`WindowIndex`],
+    /// `Update` systems using the index should run after it
+    DespawnWindows,
+    /// Decodes client messages into typed EWMH request events like
+    /// [`ev::ActivateWindowRequest`]
+    DecodeClientMessages,
+    /// Recomputes the [`WorkArea`] of monitors in [`XcbStage::Policy`],
+    /// layouts there should run after it
+    UpdateWorkAreas,
+    /// Resizes windows entering and leaving [`Fullscreen`] in
+    /// [`XcbStage::Policy`], layouts there should run after it so their sizes
+    /// win
+    Fullscreen,
+    /// Fits requested sizes to the [`SizeHints`] of windows in
+    /// `CoreStage::PostUpdate`, systems there changing [`IgnoreSizeHints`]
+    /// should run before it
+    ConstrainSize,
+}
 
-#[derive(Default)]
-pub struct XcbPlugin {}
+/// Stages the plugin adds to the app
+#[derive(StageLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum XcbStage {
+    /// Runs between `CoreStage::Update` and `CoreStage::PostUpdate`, turns
+    /// window states set during `Update` into requests which get processed
+    /// in the same frame
+    Policy,
+}
 
 impl Plugin for XcbPlugin {
     fn build(&self, builder: &mut App) {
@@ -50,30 +108,139 @@ impl Plugin for XcbPlugin {
             .add_event::<ev::MappingNotify>()
             .add_event::<ev::ScreenChangeNotify>()
             .add_event::<ev::Notify>()
+            .add_event::<ev::XError>()
+            .add_event::<ev::ActivateWindowRequest>()
+            .add_event::<ev::CloseWindowRequest>()
+            .add_event::<ev::WmStateChangeRequest>()
+            .add_event::<ev::MoveResizeRequest>()
+            .add_event::<ev::CurrentDesktopRequest>()
             .init_resource::<XConn>()
+            .init_resource::<WindowIndex>()
+            .init_resource::<WakeUp>()
+            .init_resource::<NetSupported>()
+            .init_resource::<ClientList>()
+            .init_resource::<Desktops>()
+            .insert_resource(CloseTimeout(self.close_timeout))
             .add_plugin(diagnostic::UpdateTimePlugin)
+            .add_startup_system(adopt_windows)
+            .add_startup_system(publish_wm_check)
+            .add_startup_system(discover_monitors)
             .add_system_set_to_stage(
                 CoreStage::First,
                 SystemSet::new().with_system(wait_for_xcb_events.chain(process_xcb_events)),
             )
+            // NOTE spawning happens a stage early so the new window entities
+            // already exist when `Update` systems handle events for them
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::new().with_system(spawn_windows),
+            )
+            .add_system_set_to_stage(
+                CoreStage::Update,
+                SystemSet::new()
+                    .with_system(despawn_windows.label(XcbSystem::DespawnWindows))
+                    .with_system(update_monitors),
+            )
             .add_system_set_to_stage(
                 CoreStage::Update,
                 SystemSet::new()
-                    .with_system(spawn_windows)
-                    .with_system(despawn_windows)
+                    .after(XcbSystem::DespawnWindows)
                     .with_system(map_unmanaged_windows)
                     .with_system(mark_mapped_windows)
                     .with_system(mark_unmapped_windows)
                     .with_system(mark_preffered_size_windows)
-                    .with_system(mark_size_windows),
+                    .with_system(mark_size_windows)
+                    .with_system(mark_focused_windows)
+                    .with_system(read_properties::<WindowType>)
+                    .with_system(update_properties::<WindowType>)
+                    .with_system(read_properties::<NetWmState>)
+                    .with_system(read_properties::<NetWmDesktop>)
+                    .with_system(read_struts)
+                    .with_system(update_struts)
+                    .with_system(read_properties::<SizeHints>)
+                    .with_system(update_properties::<SizeHints>)
+                    .with_system(read_properties::<WmClass>)
+                    .with_system(update_properties::<WmClass>)
+                    .with_system(decode_client_messages.label(XcbSystem::DecodeClientMessages))
+                    .with_system(move_resize_windows.after(XcbSystem::DecodeClientMessages)),
+            )
+            .add_stage_before(
+                CoreStage::PostUpdate,
+                XcbStage::Policy,
+                SystemStage::parallel(),
+            )
+            .add_system_set_to_stage(
+                XcbStage::Policy,
+                SystemSet::new()
+                    .with_system(enter_fullscreen.label(XcbSystem::Fullscreen))
+                    .with_system(exit_fullscreen.label(XcbSystem::Fullscreen))
+                    .with_system(update_work_areas.label(XcbSystem::UpdateWorkAreas)),
             )
             .add_system_set_to_stage(
                 CoreStage::PostUpdate,
                 SystemSet::new()
-                    .with_system(process_request_map)
+                    .with_system(process_request_map.before(publish_net_wm_state))
+                    .with_system(restack_net_wm_state)
+                    .with_system(restack_floating)
+                    .with_system(publish_net_wm_state)
+                    .with_system(process_withdrawn_windows)
+                    .with_system(process_request_close)
+                    .with_system(process_request_focus)
+                    .with_system(kill_unresponsive_windows)
+                    .with_system(publish_net_supported)
+                    .with_system(update_client_list)
+                    .with_system(update_client_stacking)
+                    .with_system(
+                        publish_client_list
+                            .after(update_client_list)
+                            .after(update_client_stacking),
+                    )
+                    .with_system(publish_desktops)
+                    .with_system(publish_wm_desktops)
+                    .with_system(
+                        constrain_request_size
+                            .label(XcbSystem::ConstrainSize)
+                            .before(process_request_resize),
+                    )
                     .with_system(process_request_resize),
             )
             .add_system_set_to_stage(CoreStage::Last, SystemSet::new().with_system(flush_xcb));
+
+        builder.world.resource_mut::<NetSupported>().insert(&[
+            Atom::NetClientList,
+            Atom::NetClientListStacking,
+            Atom::NetActiveWindow,
+            Atom::NetMoveresizeWindow,
+            Atom::NetWmState,
+            Atom::NetWmStateSticky,
+            Atom::NetWmStateMaximizedVert,
+            Atom::NetWmStateMaximizedHorz,
+            Atom::NetWmStateSkipTaskbar,
+            Atom::NetWmStateSkipPager,
+            Atom::NetWmStateHidden,
+            Atom::NetWmStateFullscreen,
+            Atom::NetWmStateAbove,
+            Atom::NetWmStateBelow,
+            Atom::NetWmStateDemandsAttention,
+            Atom::NetWmWindowType,
+            Atom::NetWindowTypeDesktop,
+            Atom::NetWindowTypeDock,
+            Atom::NetWindowTypeToolbar,
+            Atom::NetWindowTypeMenu,
+            Atom::NetWindowTypeUtility,
+            Atom::NetWindowTypeSplash,
+            Atom::NetWindowTypeDialog,
+            Atom::NetWindowTypeDropdownMenu,
+            Atom::NetWindowTypePopupMenu,
+            Atom::NetWindowTypeTooltip,
+            Atom::NetWindowTypeNotification,
+            Atom::NetWindowTypeCombo,
+            Atom::NetWindowTypeDnd,
+            Atom::NetWindowTypeNormal,
+            Atom::NetWmStrut,
+            Atom::NetWmStrutPartial,
+            Atom::NetWorkarea,
+        ]);
     }
 }
 
@@ -87,40 +254,120 @@ impl FromWorld for XConn {
     }
 }
 
+/// Spawns window entities for windows which already existed before the window
+/// manager started
+fn adopt_windows(
+    xconn: Res<XConn>,
+    mut index: ResMut<WindowIndex>,
+    mut client_list: ResMut<ClientList>,
+    mut commands: Commands,
+) {
+    let windows = match xconn.existing_windows() {
+        Ok(windows) => windows,
+        Err(err) => {
+            error!("failed to query existing windows: {err:#}");
+            return;
+        },
+    };
+    for existing in windows {
+        let mut entity = commands.spawn();
+        debug!("adopt window {window:?}", window = existing.window);
+        entity.insert_bundle((
+            Window(existing.window),
+            PrefferedSize(existing.region),
+            Size(existing.region),
+            Border(existing.border),
+        ));
+        if existing.is_mapped {
+            entity.insert(IsMapped);
+        }
+        if !existing.override_redirect {
+            select_window_events(&xconn, existing.window);
+            entity.insert_bundle((IsManaged, WindowType::Normal));
+            if existing.is_mapped {
+                set_wm_state(&xconn, existing.window, WmState::Normal);
+                entity.insert(WmState::Normal);
+            }
+            // windows failing the queries are likely about to be destroyed,
+            // the ones surviving keep what could be read
+            if let Err(err) = read_adopted_properties(&xconn, existing.window, &mut entity) {
+                warn!(
+                    "failed to read properties of adopted window {window:?}: {err:?}",
+                    window = existing.window
+                );
+            }
+        }
+        index.insert(existing.window, entity.id());
+        client_list.push_stacking(existing.window);
+    }
+}
+
+/// Reads the properties of an adopted window its client set before mapping it,
+/// the [`ev::MapRequest`] they are read on was long gone
+fn read_adopted_properties(
+    xconn: &XConn,
+    window: xcb::x::Window,
+    entity: &mut EntityCommands,
+) -> Result<(), ev::XError> {
+    property::insert::<WindowType>(xconn, window, entity)?;
+    property::insert::<NetWmState>(xconn, window, entity)?;
+    property::insert::<SizeHints>(xconn, window, entity)?;
+    property::insert::<WmClass>(xconn, window, entity)
+}
+
 /// Reacts to [`ev::CreateNotify`] events and spawns new window
-/// entities
-fn spawn_windows(mut events: EventReader<ev::CreateNotify>, mut commands: Commands) {
+/// entities. Skips windows which were already adopted at startup. Clients
+/// set up their windows' properties after creating them, so those are read
+/// once the windows get mapped
+fn spawn_windows(
+    xconn: Res<XConn>,
+    mut events: EventReader<ev::CreateNotify>,
+    mut index: ResMut<WindowIndex>,
+    mut commands: Commands,
+) {
     for e in events.iter() {
+        if index.get(e.window()).is_some() {
+            continue;
+        }
+        let region = Region {
+            x: e.x().into(),
+            y: e.y().into(),
+            w: e.width().into(),
+            h: e.height().into(),
+        };
         let mut entity = commands.spawn();
         debug!("spawn window {window:?}", window = e.window());
         entity.insert_bundle((
             Window(e.window()),
-            PrefferedSize(Region {
-                x: e.x().into(),
-                y: e.y().into(),
-                w: e.width().into(),
-                h: e.height().into(),
-            }),
+            PrefferedSize(region),
+            Size(region),
+            Border(e.border_width()),
         ));
         if !e.override_redirect() {
+            select_window_events(&xconn, e.window());
             entity.insert(IsManaged);
         }
+        index.insert(e.window(), entity.id());
     }
 }
 
 /// Reacts to [`ev::DestroyNotify`] events and despawns window entities with
 /// matching [`Window`]
 fn despawn_windows(
+    xconn: Res<XConn>,
     mut events: EventReader<ev::DestroyNotify>,
-    query: Query<(Entity, &Window)>,
+    mut index: ResMut<WindowIndex>,
+    focused: Query<(), With<IsFocused>>,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window) in query.iter() {
-            if window == e.window() {
-                debug!("destroy window {window:?}");
-                commands.entity(entity).despawn();
+        if let Some(entity) = index.remove(e.window()) {
+            debug!("destroy window {window:?}", window = e.window());
+            // destroyed windows get no FocusOut
+            if focused.get(entity).is_ok() {
+                set_active_window(&xconn, None);
             }
+            commands.entity(entity).despawn();
         }
     }
 }
@@ -129,13 +376,14 @@ fn despawn_windows(
 /// unconditionally as WMs are supposed to
 fn map_unmanaged_windows(
     mut events: EventReader<ev::MapRequest>,
-    query: Query<(Entity, &Window), (Without<IsMapped>, Without<IsManaged>)>,
+    lookup: EntityLookup,
+    query: Query<(), (Without<IsMapped>, Without<IsManaged>)>,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window) in query.iter() {
-            if window == e.window() {
-                debug!("map unmanaged window {window:?}");
+        if let Some(entity) = lookup.get(e.window()) {
+            if query.get(entity).is_ok() {
+                debug!("map unmanaged window {window:?}", window = e.window());
                 commands.entity(entity).insert(RequestMap::Map);
             }
         }
@@ -146,88 +394,214 @@ fn map_unmanaged_windows(
 /// [`RequestMap`] if present
 fn mark_mapped_windows(
     mut events: EventReader<ev::MapNotify>,
-    query: Query<(Entity, &Window)>,
+    lookup: EntityLookup,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window) in query.iter() {
-            if window == e.window() {
-                commands
-                    .entity(entity)
-                    .remove::<RequestMap>()
-                    .insert(IsMapped);
-            }
+        if let Some(entity) = lookup.get(e.window()) {
+            commands
+                .entity(entity)
+                .remove::<RequestMap>()
+                .insert(IsMapped);
         }
     }
 }
 
 /// Reacts to [`ev::UnmapNotify`], removes [`IsMapped`] marker and clears
 /// [`RequestMap`] if present
+///
+/// Windows in [`WmState::Normal`] weren't unmapped by us so their clients
+/// withdrew them, as did clients sending a synthetic `UnmapNotify`. Either way
+/// the window is marked [`WmState::Withdrawn`] and no longer [`Closing`], some
+/// clients withdraw their windows rather than destroying them when asked to
+/// close
 fn mark_unmapped_windows(
     mut events: EventReader<ev::UnmapNotify>,
-    query: Query<(Entity, &Window)>,
+    lookup: EntityLookup,
+    query: Query<&WmState>,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window) in query.iter() {
-            if window == e.window() {
-                commands
-                    .entity(entity)
-                    .remove_bundle::<(RequestMap, IsMapped)>();
+        if let Some(entity) = lookup.get(e.window()) {
+            let mut entity_commands = commands.entity(entity);
+            entity_commands.remove_bundle::<(RequestMap, IsMapped)>();
+
+            let is_synthetic = e.response_type() & 0x80 != 0;
+            let is_withdrawn = match query.get(entity) {
+                Ok(WmState::Normal) => true,
+                Ok(WmState::Iconic) => is_synthetic,
+                _ => false,
+            };
+            if is_withdrawn {
+                entity_commands
+                    .insert(WmState::Withdrawn)
+                    .remove::<Closing>();
             }
         }
     }
 }
 
 /// Reacts to [`ev::ConfigureRequest`], updates window's preferred
-/// size. If the window is not marked [`IsManaged`] it'll also add
-/// [`RequestConfigure`]
+/// size. If the window is not marked [`IsManaged`] or is [`Floating`] it'll
+/// also add [`RequestSize`] and [`RequestBorder`], managed windows get
+/// [`RequestConfigureNotify`] in case their request ends up denied or altered
 fn mark_preffered_size_windows(
     mut events: EventReader<ev::ConfigureRequest>,
-    query: Query<(Entity, &Window, Option<&IsManaged>)>,
+    lookup: EntityLookup,
+    query: Query<(Option<&IsManaged>, Option<&Floating>)>,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window, is_managed) in query.iter() {
-            if window == e.window() {
-                let region = Region {
-                    x: e.x().into(),
-                    y: e.y().into(),
-                    w: e.width().into(),
-                    h: e.height().into(),
-                };
-                let border = e.border_width();
-                let mut entity = commands.entity(entity);
-                entity.insert_bundle((PrefferedSize(region), PrefferedBorder(border)));
-                if is_managed.is_none() {
-                    entity.insert_bundle((RequestSize(region), RequestBorder(border)));
-                }
+        let entity = match lookup.get(e.window()) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        if let Ok((is_managed, floating)) = query.get(entity) {
+            let region = Region {
+                x: e.x().into(),
+                y: e.y().into(),
+                w: e.width().into(),
+                h: e.height().into(),
+            };
+            let border = e.border_width();
+            let mut entity = commands.entity(entity);
+            entity.insert_bundle((PrefferedSize(region), PrefferedBorder(border)));
+            if is_managed.is_none() || floating.is_some() {
+                entity.insert_bundle((RequestSize(region), RequestBorder(border)));
+            }
+            if is_managed.is_some() {
+                entity.insert(RequestConfigureNotify);
             }
         }
     }
 }
 
+/// Reacts to [`ev::MoveResizeRequest`] the way [`mark_preffered_size_windows`]
+/// does to configure requests, fields left out keep the window's current
+/// geometry. The position is the one of the reference point the gravity
+/// names, falling back to the gravity of the window's [`SizeHints`]
+fn move_resize_windows(
+    mut events: EventReader<ev::MoveResizeRequest>,
+    query: Query<(
+        &Size,
+        &Border,
+        Option<&SizeHints>,
+        Option<&IsManaged>,
+        Option<&Floating>,
+    )>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        let (&Size(size), &Border(border), hints, is_managed, floating) = match query.get(e.entity)
+        {
+            Ok(window) => window,
+            Err(_) => continue,
+        };
+        let (w, h) = (e.w.unwrap_or(size.w), e.h.unwrap_or(size.h));
+        let gravity = match e.gravity {
+            0 => hints.and_then(|hints| hints.gravity).unwrap_or(1),
+            gravity => gravity,
+        };
+        // gravities run north west to south east, row by row
+        let (column, row) = match gravity {
+            gravity @ 1..=9 => (i32::from((gravity - 1) % 3), i32::from((gravity - 1) / 3)),
+            _ => (0, 0),
+        };
+        let outer = |length: u32| (length + 2 * u32::from(border)) as i32;
+        let region = Region {
+            x: e.x.map_or(size.x, |x| x - column * outer(w) / 2),
+            y: e.y.map_or(size.y, |y| y - row * outer(h) / 2),
+            w,
+            h,
+        };
+        debug!(
+            "move and resize window {window:?} to {region:?}",
+            window = e.window
+        );
+        let mut entity = commands.entity(e.entity);
+        entity.insert(PrefferedSize(region));
+        if is_managed.is_none() || floating.is_some() {
+            entity.insert(RequestSize(region));
+        }
+        if is_managed.is_some() {
+            entity.insert(RequestConfigureNotify);
+        }
+    }
+}
+
 /// Reacts to [`ev::ConfigureNotify`] events and updates window's actual
 /// size [`Size`]
 fn mark_size_windows(
     mut events: EventReader<ev::ConfigureNotify>,
-    query: Query<(Entity, &Window)>,
+    lookup: EntityLookup,
     mut commands: Commands,
 ) {
     for e in events.iter() {
-        for (entity, &window) in query.iter() {
-            if window == e.window() {
-                let region = Region {
-                    x: e.x().into(),
-                    y: e.y().into(),
-                    w: e.width().into(),
-                    h: e.height().into(),
-                };
-                let border = e.border_width();
-                commands
-                    .entity(entity)
-                    .insert_bundle((Size(region), Border(border)));
-            }
+        if let Some(entity) = lookup.get(e.window()) {
+            let region = Region {
+                x: e.x().into(),
+                y: e.y().into(),
+                w: e.width().into(),
+                h: e.height().into(),
+            };
+            let border = e.border_width();
+            commands
+                .entity(entity)
+                .insert_bundle((Size(region), Border(border)));
         }
     }
 }
+
+/// Reacts to [`ev::FocusIn`] and [`ev::FocusOut`] so the [`IsFocused`] marker
+/// and `_NET_ACTIVE_WINDOW` follow clients taking focus themselves
+fn mark_focused_windows(
+    xconn: Res<XConn>,
+    mut ev_focus_in: EventReader<ev::FocusIn>,
+    mut ev_focus_out: EventReader<ev::FocusOut>,
+    lookup: EntityLookup,
+    managed: Query<(), With<IsManaged>>,
+    focused: Query<(Entity, &Window), With<IsFocused>>,
+    mut commands: Commands,
+) {
+    let is_relevant = |mode, detail| {
+        mode != xcb::x::NotifyMode::Grab
+            && mode != xcb::x::NotifyMode::Ungrab
+            && detail != xcb::x::NotifyDetail::Inferior
+            && detail != xcb::x::NotifyDetail::Pointer
+    };
+    let focused_window = focused.iter().next();
+
+    // a FocusOut comes before the matching FocusIn, which overrides it
+    let mut focus = None;
+    for e in ev_focus_out.iter() {
+        if is_relevant(e.mode(), e.detail())
+            && focused_window.is_some_and(|(_, &Window(window))| window == e.event())
+        {
+            focus = Some(None);
+        }
+    }
+    for e in ev_focus_in.iter() {
+        if !is_relevant(e.mode(), e.detail()) {
+            continue;
+        }
+        if let Some(entity) = lookup.get(e.event()).filter(|&e| managed.get(e).is_ok()) {
+            focus = Some(Some((entity, e.event())));
+        }
+    }
+
+    let focus = match focus {
+        Some(focus) => focus,
+        None => return,
+    };
+    if focus.map(|(entity, _)| entity) == focused_window.map(|(entity, _)| entity) {
+        return;
+    }
+    debug!("focus changed to {focus:?}");
+    for (entity, _) in focused.iter() {
+        commands.entity(entity).remove::<IsFocused>();
+    }
+    if let Some((entity, _)) = focus {
+        commands.entity(entity).insert(IsFocused);
+    }
+    set_active_window(&xconn, focus.map(|(_, window)| window));
+}
@@ -0,0 +1,122 @@
+use std::fmt::Debug;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::EntityCommands;
+use log::debug;
+
+use crate::atom::Atom;
+use crate::component::*;
+use crate::event as ev;
+use crate::window_index::EntityLookup;
+use crate::xconn::XConn;
+
+/// Component mirroring properties clients set on their windows
+///
+/// Clients set up their windows after creating them, so the properties of
+/// managed windows are read once they ask to be mapped by [`read_properties`]
+/// and, for properties clients may change later, kept up to date by
+/// [`update_properties`]
+pub(crate) trait WindowProperty: Component + PartialEq + Debug + Sized {
+    /// Properties the component is read from
+    const ATOMS: &'static [Atom];
+
+    /// Reads the component, `None` if the window doesn't set the properties
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError>;
+
+    /// Puts the component read on the window entity
+    fn insert(self, entity: &mut EntityCommands) {
+        entity.insert(self);
+    }
+
+    /// Drops the component once the window no longer sets the properties
+    fn remove(entity: &mut EntityCommands) {
+        entity.remove::<Self>();
+    }
+}
+
+/// Inserts the component read from the window's properties unless it's
+/// unchanged, or removes it once the properties are gone
+pub(crate) fn refresh<P: WindowProperty>(
+    xconn: &XConn,
+    entity: Entity,
+    window: xcb::x::Window,
+    current: Option<&P>,
+    commands: &mut Commands,
+) -> Result<(), ev::XError> {
+    let value = P::read(xconn, window)?;
+    if value.as_ref() != current {
+        debug!("window {window:?} has {value:?}");
+        let mut entity = commands.entity(entity);
+        match value {
+            Some(value) => value.insert(&mut entity),
+            None => P::remove(&mut entity),
+        }
+    }
+    Ok(())
+}
+
+/// Inserts the component read from the window's properties if it sets them
+pub(crate) fn insert<P: WindowProperty>(
+    xconn: &XConn,
+    window: xcb::x::Window,
+    entity: &mut EntityCommands,
+) -> Result<(), ev::XError> {
+    if let Some(value) = P::read(xconn, window)? {
+        value.insert(entity);
+    }
+    Ok(())
+}
+
+/// Reacts to [`ev::MapRequest`] of managed windows and reads the properties
+/// their clients set before mapping them
+pub fn read_properties<P: WindowProperty>(
+    xconn: Res<XConn>,
+    mut events: EventReader<ev::MapRequest>,
+    lookup: EntityLookup,
+    query: Query<Option<&P>, With<IsManaged>>,
+    mut ev_x_error: EventWriter<ev::XError>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        let entity = match lookup.get(e.window()) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        if let Ok(current) = query.get(entity) {
+            if let Err(error) = refresh(&xconn, entity, e.window(), current, &mut commands) {
+                ev_x_error.send(error);
+            }
+        }
+    }
+}
+
+/// Reacts to [`ev::PropertyNotify`] events about the properties of managed
+/// windows and refreshes the component
+pub fn update_properties<P: WindowProperty>(
+    xconn: Res<XConn>,
+    mut events: EventReader<ev::PropertyNotify>,
+    lookup: EntityLookup,
+    query: Query<Option<&P>, With<IsManaged>>,
+    mut ev_x_error: EventWriter<ev::XError>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        if !is_about::<P>(&xconn, e) {
+            continue;
+        }
+        let entity = match lookup.get(e.window()) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        if let Ok(current) = query.get(entity) {
+            if let Err(error) = refresh(&xconn, entity, e.window(), current, &mut commands) {
+                ev_x_error.send(error);
+            }
+        }
+    }
+}
+
+/// Whether the event is about one of the properties the component is read from
+pub(crate) fn is_about<P: WindowProperty>(xconn: &XConn, e: &ev::PropertyNotify) -> bool {
+    P::ATOMS.iter().any(|&atom| xconn.atom(atom) == e.atom())
+}
@@ -0,0 +1,133 @@
+use bevy_ecs::prelude::*;
+
+use crate::backend::PropertyData;
+use crate::component::*;
+use crate::property::WindowProperty;
+use crate::request::*;
+use crate::xconn::XConn;
+use crate::{event as ev, Atom, Region};
+
+// `WM_SIZE_HINTS` flags telling which fields are set
+const P_MIN_SIZE: u32 = 1 << 4;
+const P_MAX_SIZE: u32 = 1 << 5;
+const P_RESIZE_INC: u32 = 1 << 6;
+const P_ASPECT: u32 = 1 << 7;
+const P_BASE_SIZE: u32 = 1 << 8;
+const P_WIN_GRAVITY: u32 = 1 << 9;
+
+/// Read from `WM_NORMAL_HINTS`, clients predating ICCCM 1.0 don't send base
+/// size and gravity
+impl WindowProperty for SizeHints {
+    const ATOMS: &'static [Atom] = &[Atom::WmNormalHints];
+
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError> {
+        let v = match xconn.get_property(window, xconn.atom(Atom::WmNormalHints))? {
+            Some(PropertyData::U32(v)) if v.len() >= 15 => v,
+            _ => return Ok(None),
+        };
+        let flags = v[0];
+        let pair = |flag, i: usize| {
+            (flags & flag != 0 && v.len() > i + 1 && (v[i] != 0 || v[i + 1] != 0))
+                .then(|| (v[i], v[i + 1]))
+        };
+        let hints = SizeHints {
+            min: pair(P_MIN_SIZE, 5),
+            max: pair(P_MAX_SIZE, 7),
+            increment: pair(P_RESIZE_INC, 9),
+            aspect: pair(P_ASPECT, 11).zip(pair(P_ASPECT, 13)),
+            base: pair(P_BASE_SIZE, 15),
+            gravity: (flags & P_WIN_GRAVITY != 0 && v.len() >= 18).then(|| v[17] as u8),
+        };
+        Ok((hints != SizeHints::default()).then_some(hints))
+    }
+}
+
+impl SizeHints {
+    /// Returns the largest size within the region the hints allow, minimum
+    /// sizes win over the region. The window is placed by its gravity,
+    /// constraints in `ignored` are left out
+    pub fn constrain(&self, region: Region, ignored: IgnoreSizeHints) -> Region {
+        // NOTE as in ICCCM 4.1.2.3 base and minimum size stand in for each
+        // other, the base only counts towards the aspect ratio if it differs
+        let (mut min_w, mut min_h) = self.min.or(self.base).unwrap_or((1, 1));
+        let (mut base_w, mut base_h) = self.base.or(self.min).unwrap_or((0, 0));
+        let base_is_min = self.min.or(self.base) == Some((base_w, base_h));
+        // without a minimum the base can't make the window outgrow the region
+        if ignored.contains(IgnoreSizeHints::MIN) {
+            (min_w, min_h) = (1, 1);
+            (base_w, base_h) = (base_w.min(region.w), base_h.min(region.h));
+        }
+
+        let (mut w, mut h) = (region.w, region.h);
+        if !base_is_min {
+            w = w.saturating_sub(base_w);
+            h = h.saturating_sub(base_h);
+        }
+        let aspect = self
+            .aspect
+            .filter(|_| !ignored.contains(IgnoreSizeHints::ASPECT));
+        if let Some(((min_x, min_y), (max_x, max_y))) = aspect {
+            let ratio = f64::from(w) / f64::from(h.max(1));
+            if max_y > 0 && ratio > f64::from(max_x) / f64::from(max_y) {
+                w = (f64::from(h) * f64::from(max_x) / f64::from(max_y)).round() as u32;
+            } else if min_x > 0 && ratio < f64::from(min_x) / f64::from(min_y.max(1)) {
+                h = (f64::from(w) * f64::from(min_y) / f64::from(min_x)).round() as u32;
+            }
+        }
+        if base_is_min {
+            w = w.saturating_sub(base_w);
+            h = h.saturating_sub(base_h);
+        }
+        let increment = self
+            .increment
+            .filter(|_| !ignored.contains(IgnoreSizeHints::INCREMENT));
+        if let Some((inc_w, inc_h)) = increment {
+            w -= w % inc_w.max(1);
+            h -= h % inc_h.max(1);
+        }
+        w = (w + base_w).max(min_w).max(1);
+        h = (h + base_h).max(min_h).max(1);
+        let max = self.max.filter(|_| !ignored.contains(IgnoreSizeHints::MAX));
+        if let Some((max_w, max_h)) = max {
+            if max_w > 0 {
+                w = w.min(max_w);
+            }
+            if max_h > 0 {
+                h = h.min(max_h);
+            }
+        }
+
+        // gravities run north west to south east, row by row
+        let (dx, dy) = (
+            i64::from(region.w) - i64::from(w),
+            i64::from(region.h) - i64::from(h),
+        );
+        let (column, row) = match self.gravity {
+            Some(gravity @ 1..=9) => ((gravity - 1) % 3, (gravity - 1) / 3),
+            _ => (0, 0),
+        };
+        Region {
+            x: (i64::from(region.x) + dx * i64::from(column) / 2) as i32,
+            y: (i64::from(region.y) + dy * i64::from(row) / 2) as i32,
+            w,
+            h,
+        }
+    }
+}
+
+/// Fits [`RequestSize`] of managed windows to their [`SizeHints`] before it
+/// gets sent, but for the [`IgnoreSizeHints`] constraints. Fullscreen windows
+/// are left alone
+pub fn constrain_request_size(
+    mut query: Query<
+        (&SizeHints, Option<&IgnoreSizeHints>, &mut RequestSize),
+        (With<IsManaged>, Without<Fullscreen>, Changed<RequestSize>),
+    >,
+) {
+    for (hints, ignored, mut request) in query.iter_mut() {
+        let constrained = hints.constrain(request.0, ignored.copied().unwrap_or_default());
+        if constrained != request.0 {
+            request.0 = constrained;
+        }
+    }
+}
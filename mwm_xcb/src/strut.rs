@@ -0,0 +1,194 @@
+use bevy_ecs::prelude::*;
+use log::debug;
+
+use crate::atom::Atom;
+use crate::backend::{PropertyData, XRequest};
+use crate::component::*;
+use crate::ewmh::Desktops;
+use crate::property::{self, WindowProperty};
+use crate::window_index::EntityLookup;
+use crate::xconn::XConn;
+use crate::{event as ev, Region};
+
+/// `_NET_WM_STRUT_PARTIAL` is preferred over `_NET_WM_STRUT` as the spec asks
+impl WindowProperty for Strut {
+    const ATOMS: &'static [Atom] = &[Atom::NetWmStrutPartial, Atom::NetWmStrut];
+
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError> {
+        let partial = xconn.get_property(window, xconn.atom(Atom::NetWmStrutPartial))?;
+        if let Some(PropertyData::U32(v)) = partial {
+            if v.len() >= 12 {
+                return Ok(Some(Strut {
+                    left: v[0],
+                    right: v[1],
+                    top: v[2],
+                    bottom: v[3],
+                    left_range: (v[4], v[5]),
+                    right_range: (v[6], v[7]),
+                    top_range: (v[8], v[9]),
+                    bottom_range: (v[10], v[11]),
+                }));
+            }
+        }
+
+        let strut = xconn.get_property(window, xconn.atom(Atom::NetWmStrut))?;
+        if let Some(PropertyData::U32(v)) = strut {
+            if v.len() >= 4 {
+                return Ok(Some(Strut {
+                    left: v[0],
+                    right: v[1],
+                    top: v[2],
+                    bottom: v[3],
+                    left_range: (0, u32::MAX),
+                    right_range: (0, u32::MAX),
+                    top_range: (0, u32::MAX),
+                    bottom_range: (0, u32::MAX),
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Reads the struts of windows which became docks and drops them from
+/// windows which no longer are
+pub fn read_struts(
+    xconn: Res<XConn>,
+    query: Query<(Entity, &Window, &WindowType, Option<&Strut>), Changed<WindowType>>,
+    mut ev_x_error: EventWriter<ev::XError>,
+    mut commands: Commands,
+) {
+    for (entity, &Window(window), &window_type, strut) in query.iter() {
+        if window_type == WindowType::Dock {
+            if let Err(error) = property::refresh(&xconn, entity, window, strut, &mut commands) {
+                ev_x_error.send(error);
+            }
+        } else if strut.is_some() {
+            commands.entity(entity).remove::<Strut>();
+        }
+    }
+}
+
+/// Reacts to [`ev::PropertyNotify`] events about the struts of docks and
+/// updates their [`Strut`]
+pub fn update_struts(
+    xconn: Res<XConn>,
+    mut events: EventReader<ev::PropertyNotify>,
+    lookup: EntityLookup,
+    query: Query<(&WindowType, Option<&Strut>)>,
+    mut ev_x_error: EventWriter<ev::XError>,
+    mut commands: Commands,
+) {
+    for e in events.iter() {
+        if !property::is_about::<Strut>(&xconn, e) {
+            continue;
+        }
+        let entity = match lookup.get(e.window()) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        if let Ok((WindowType::Dock, strut)) = query.get(entity) {
+            if let Err(error) = property::refresh(&xconn, entity, e.window(), strut, &mut commands)
+            {
+                ev_x_error.send(error);
+            }
+        }
+    }
+}
+
+/// Recomputes the [`WorkArea`] of every monitor from the struts of mapped
+/// docks and publishes the one of the whole screen as `_NET_WORKAREA`, once
+/// per desktop
+pub fn update_work_areas(
+    xconn: Res<XConn>,
+    desktops: Res<Desktops>,
+    struts: Query<&Strut, With<IsMapped>>,
+    mut monitors: Query<(&Size, &mut WorkArea), With<Monitor>>,
+    mut published: Local<Option<(Region, usize)>>,
+) {
+    let screen = match monitors
+        .iter()
+        .map(|(&Size(region), _)| region)
+        .reduce(bounding_box)
+    {
+        Some(screen) => screen,
+        None => return,
+    };
+
+    for (&Size(region), mut work_area) in monitors.iter_mut() {
+        let usable = usable_region(region, screen, struts.iter());
+        if work_area.0 != usable {
+            debug!("work area of monitor {region:?} is now {usable:?}");
+            work_area.0 = usable;
+        }
+    }
+
+    let usable = usable_region(screen, screen, struts.iter());
+    let count = desktops.names.len().max(1);
+    if *published != Some((usable, count)) {
+        *published = Some((usable, count));
+        xconn.send(XRequest::ChangeProperty {
+            window: xconn.root(),
+            property: xconn.atom(Atom::NetWorkarea),
+            r#type: xcb::x::ATOM_CARDINAL,
+            data: PropertyData::U32(
+                [usable.x as u32, usable.y as u32, usable.w, usable.h].repeat(count),
+            ),
+        });
+    }
+}
+
+/// Smallest region containing both
+fn bounding_box(a: Region, b: Region) -> Region {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let right = (a.x + a.w as i32).max(b.x + b.w as i32);
+    let bottom = (a.y + a.h as i32).max(b.y + b.h as i32);
+    Region {
+        x,
+        y,
+        w: (right - x) as u32,
+        h: (bottom - y) as u32,
+    }
+}
+
+/// Shrinks the region by the struts overlapping it
+fn usable_region<'a>(
+    region: Region,
+    screen: Region,
+    struts: impl Iterator<Item = &'a Strut>,
+) -> Region {
+    // whether the inclusive range overlaps the half open one
+    let overlaps = |(start, end): (u32, u32), from: i32, len: u32| {
+        i64::from(start) < i64::from(from) + i64::from(len) && i64::from(end) >= i64::from(from)
+    };
+
+    let mut left = i64::from(region.x);
+    let mut right = i64::from(region.x) + i64::from(region.w);
+    let mut top = i64::from(region.y);
+    let mut bottom = i64::from(region.y) + i64::from(region.h);
+    let screen_right = i64::from(screen.x) + i64::from(screen.w);
+    let screen_bottom = i64::from(screen.y) + i64::from(screen.h);
+
+    for strut in struts {
+        if strut.left > 0 && overlaps(strut.left_range, region.y, region.h) {
+            left = left.max(i64::from(screen.x) + i64::from(strut.left));
+        }
+        if strut.right > 0 && overlaps(strut.right_range, region.y, region.h) {
+            right = right.min(screen_right - i64::from(strut.right));
+        }
+        if strut.top > 0 && overlaps(strut.top_range, region.x, region.w) {
+            top = top.max(i64::from(screen.y) + i64::from(strut.top));
+        }
+        if strut.bottom > 0 && overlaps(strut.bottom_range, region.x, region.w) {
+            bottom = bottom.min(screen_bottom - i64::from(strut.bottom));
+        }
+    }
+
+    Region {
+        x: left as i32,
+        y: top as i32,
+        w: (right - left).max(0) as u32,
+        h: (bottom - top).max(0) as u32,
+    }
+}
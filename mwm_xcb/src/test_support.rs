@@ -0,0 +1,53 @@
+//! Shared fixtures for tests running apps on a [`FakeBackend`]
+
+use bevy_app::App;
+use bevy_ecs::prelude::*;
+
+use crate::backend::MonitorInfo;
+use crate::fake::FakeBackend;
+use crate::{Region, WindowIndex, XConn, XcbPlugin};
+
+/// Creates an app running the [`XcbPlugin`] on the fake X server, policy
+/// plugins go on top
+pub fn app(fake: &FakeBackend) -> App {
+    app_with(fake, XcbPlugin::default())
+}
+
+/// Creates an app running the given [`XcbPlugin`] on the fake X server
+pub fn app_with(fake: &FakeBackend, plugin: XcbPlugin) -> App {
+    let mut app = App::new();
+    app.insert_resource(XConn::new(fake.clone()))
+        .add_plugin(plugin);
+    app
+}
+
+/// Updates the app the given number of frames
+pub fn run(app: &mut App, frames: usize) {
+    for _ in 0..frames {
+        app.update();
+    }
+}
+
+/// Entity of the window, if it got spawned
+pub fn entity(app: &App, window: xcb::x::Window) -> Option<Entity> {
+    app.world.resource::<WindowIndex>().get(window)
+}
+
+/// Lets the client of the created window ask to map it and gives the
+/// policy a few frames to react
+pub fn map_window(app: &mut App, fake: &FakeBackend, window: xcb::x::Window) -> Entity {
+    run(app, 1);
+    fake.request_map(window);
+    run(app, 3);
+    entity(app, window).unwrap()
+}
+
+/// Sets up the fake X server with a single primary monitor covering the region
+pub fn single_monitor(fake: &FakeBackend, region: Region) {
+    fake.set_monitors(vec![MonitorInfo {
+        name: "eDP-1".into(),
+        region,
+        primary: true,
+        refresh_rate: None,
+    }]);
+}
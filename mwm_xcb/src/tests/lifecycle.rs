@@ -0,0 +1,496 @@
+//! Runs the [`crate::XcbPlugin`] on a [`FakeBackend`] through the window
+//! lifecycle
+
+use std::time::Duration;
+
+use bevy_app::App;
+use bevy_ecs::prelude::*;
+use xcb::Xid;
+
+use crate::backend::{Backend, MonitorInfo, PropertyData, XRequest};
+use crate::component::*;
+use crate::fake::FakeBackend;
+use crate::request::*;
+use crate::test_support::{app_with, entity, run};
+use crate::{Atom, Region, XcbPlugin};
+
+const REGION: Region = Region { x: 10, y: 20, w: 300, h: 200 };
+
+fn app(fake: &FakeBackend, close_timeout: Option<Duration>) -> App {
+    let mut app = app_with(fake, XcbPlugin { close_timeout });
+    run(&mut app, 1);
+    app
+}
+
+/// Creates a managed window and maps it the way a window manager policy would
+fn mapped_window(app: &mut App, fake: &FakeBackend) -> (xcb::x::Window, Entity) {
+    let window = fake.create_window(REGION, false);
+    run(app, 1);
+    let entity = entity(app, window).unwrap();
+    fake.request_map(window);
+    run(app, 1);
+    app.world.entity_mut(entity).insert(RequestMap::Map);
+    run(app, 2);
+    fake.take_requests();
+    (window, entity)
+}
+
+#[test]
+fn spawns_managed_and_unmanaged_windows() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let managed = fake.create_window(REGION, false);
+    let popup = fake.create_window(REGION, true);
+    run(&mut app, 1);
+
+    let managed = app.world.entity(entity(&app, managed).unwrap());
+    assert!(managed.contains::<IsManaged>());
+    assert_eq!(managed.get::<Size>().unwrap().0, REGION);
+    let popup = app.world.entity(entity(&app, popup).unwrap());
+    assert!(!popup.contains::<IsManaged>());
+}
+
+#[test]
+fn adopts_existing_windows_with_their_properties() {
+    let fake = FakeBackend::new();
+    let window = fake.add_existing_window(REGION, false, true);
+    let dialog = fake.atom(Atom::NetWindowTypeDialog).resource_id();
+    fake.set_property(
+        window,
+        Atom::NetWmWindowType,
+        PropertyData::U32(vec![dialog]),
+    );
+    fake.set_property(
+        window,
+        Atom::WmClass,
+        PropertyData::U8(b"term\0Terminal\0".to_vec()),
+    );
+    let app = app(&fake, None);
+
+    let entity = app.world.entity(entity(&app, window).unwrap());
+    assert!(entity.contains::<IsManaged>());
+    assert!(entity.contains::<IsMapped>());
+    assert_eq!(entity.get::<WmState>(), Some(&WmState::Normal));
+    assert_eq!(entity.get::<WindowType>(), Some(&WindowType::Dialog));
+    assert_eq!(entity.get::<WmClass>().unwrap().class, "Terminal");
+}
+
+#[test]
+fn maps_unmanaged_windows_on_request() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let popup = fake.create_window(REGION, true);
+    run(&mut app, 1);
+    fake.request_map(popup);
+    run(&mut app, 2);
+
+    assert!(fake.window(popup).unwrap().is_mapped);
+    assert!(app
+        .world
+        .entity(entity(&app, popup).unwrap())
+        .contains::<IsMapped>());
+}
+
+#[test]
+fn maps_and_iconifies_managed_windows() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let window = fake.create_window(REGION, false);
+    run(&mut app, 1);
+    fake.request_map(window);
+    run(&mut app, 2);
+    // mapping managed windows is up to the policy
+    assert!(!fake.window(window).unwrap().is_mapped);
+
+    let (window, entity) = mapped_window(&mut app, &fake);
+    assert!(fake.window(window).unwrap().is_mapped);
+    let mapped = app.world.entity(entity);
+    assert!(mapped.contains::<IsMapped>());
+    assert_eq!(mapped.get::<WmState>(), Some(&WmState::Normal));
+    assert_eq!(
+        fake.property(window, Atom::WmState),
+        Some(PropertyData::U32(vec![WmState::Normal as u32, 0]))
+    );
+
+    app.world.entity_mut(entity).insert(RequestMap::Unmap);
+    run(&mut app, 2);
+    assert!(!fake.window(window).unwrap().is_mapped);
+    let unmapped = app.world.entity(entity);
+    assert!(!unmapped.contains::<IsMapped>());
+    assert_eq!(unmapped.get::<WmState>(), Some(&WmState::Iconic));
+    assert!(unmapped
+        .get::<NetWmState>()
+        .unwrap()
+        .contains(NetWmState::HIDDEN));
+}
+
+#[test]
+fn withdraws_windows_unmapped_by_their_client() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    fake.push_event(xcb::x::Event::UnmapNotify(xcb::x::UnmapNotifyEvent::new(
+        window, window, false,
+    )));
+    run(&mut app, 2);
+
+    assert_eq!(
+        app.world.entity(entity).get::<WmState>(),
+        Some(&WmState::Withdrawn)
+    );
+    assert_eq!(fake.property(window, Atom::WmState), None);
+}
+
+#[test]
+fn forwards_configure_requests_of_unmanaged_windows() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let popup = fake.create_window(REGION, true);
+    run(&mut app, 1);
+    let region = Region { x: 50, y: 60, w: 70, h: 80 };
+    fake.request_configure(popup, region, 2);
+    run(&mut app, 2);
+
+    let popup_window = fake.window(popup).unwrap();
+    assert_eq!((popup_window.region, popup_window.border), (region, 2));
+    let entity = app.world.entity(entity(&app, popup).unwrap());
+    assert_eq!(entity.get::<Size>().unwrap().0, region);
+    assert_eq!(entity.get::<Border>().unwrap().0, 2);
+}
+
+#[test]
+fn notifies_managed_windows_of_denied_configure_requests() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    let region = Region { x: 50, y: 60, w: 70, h: 80 };
+    fake.request_configure(window, region, 2);
+    run(&mut app, 2);
+
+    assert_eq!(fake.window(window).unwrap().region, REGION);
+    assert_eq!(
+        app.world.entity(entity).get::<PrefferedSize>().unwrap().0,
+        region
+    );
+    assert!(fake.take_requests().iter().any(|request| matches!(
+        request,
+        XRequest::SendConfigureNotify { window: w, region, border: 0 }
+            if *w == window && *region == REGION
+    )));
+}
+
+// `_NET_MOVERESIZE_WINDOW` flags for all of x, y, width and height given
+const MOVE_RESIZE: u32 = 0xf << 8;
+
+#[test]
+fn moves_and_resizes_floating_windows_on_request() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    app.world.entity_mut(entity).insert(Floating);
+    run(&mut app, 1);
+    fake.send_client_message(window, Atom::NetMoveresizeWindow, [
+        MOVE_RESIZE | 1,
+        50,
+        60,
+        70,
+        80,
+    ]);
+    run(&mut app, 2);
+    assert_eq!(fake.window(window).unwrap().region, Region {
+        x: 50,
+        y: 60,
+        w: 70,
+        h: 80
+    });
+
+    // south east gravity, the position is the bottom right corner
+    fake.send_client_message(window, Atom::NetMoveresizeWindow, [
+        MOVE_RESIZE | 9,
+        200,
+        300,
+        70,
+        80,
+    ]);
+    run(&mut app, 2);
+    assert_eq!(fake.window(window).unwrap().region, Region {
+        x: 130,
+        y: 220,
+        w: 70,
+        h: 80
+    });
+}
+
+#[test]
+fn keeps_tiled_windows_in_place_on_move_resize_requests() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    // only the width given
+    fake.send_client_message(window, Atom::NetMoveresizeWindow, [1 << 10, 0, 0, 70, 0]);
+    run(&mut app, 2);
+
+    assert_eq!(fake.window(window).unwrap().region, REGION);
+    assert_eq!(
+        app.world.entity(entity).get::<PrefferedSize>().unwrap().0,
+        Region { w: 70, ..REGION }
+    );
+    assert!(fake.take_requests().iter().any(
+        |request| matches!(request, XRequest::SendConfigureNotify { window: w, .. } if *w == window)
+    ));
+}
+
+#[test]
+fn despawns_destroyed_windows() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    fake.destroy_window(window);
+    run(&mut app, 2);
+
+    assert_eq!(self::entity(&app, window), None);
+    assert!(app.world.get_entity(entity).is_none());
+}
+
+#[test]
+fn kills_clients_without_delete_window_on_close() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    app.world.entity_mut(entity).insert(RequestClose);
+    run(&mut app, 2);
+
+    assert!(kills(&fake.requests(), window));
+    assert!(fake.window(window).is_none());
+    assert!(app.world.get_entity(entity).is_none());
+}
+
+#[test]
+fn asks_clients_to_close_and_kills_them_after_the_timeout() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, Some(Duration::ZERO));
+    let (window, entity) = mapped_window(&mut app, &fake);
+    let delete_window = fake.atom(Atom::WmDeleteWindow).resource_id();
+    fake.set_property(
+        window,
+        Atom::WmProtocols,
+        PropertyData::U32(vec![delete_window]),
+    );
+    app.world.entity_mut(entity).insert(RequestClose);
+    run(&mut app, 1);
+
+    let requests = fake.take_requests();
+    assert!(requests.iter().any(|request| matches!(
+        request,
+        XRequest::SendClientMessage { window: w, data, .. }
+            if *w == window && data[0] == delete_window
+    )));
+    assert!(!kills(&requests, window));
+    assert!(app.world.entity(entity).contains::<Closing>());
+
+    run(&mut app, 2);
+    assert!(kills(&fake.requests(), window));
+    assert!(app.world.get_entity(entity).is_none());
+}
+
+#[test]
+fn spares_clients_withdrawing_their_window_on_close() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, Some(Duration::ZERO));
+    let (window, entity) = mapped_window(&mut app, &fake);
+    let delete_window = fake.atom(Atom::WmDeleteWindow).resource_id();
+    fake.set_property(
+        window,
+        Atom::WmProtocols,
+        PropertyData::U32(vec![delete_window]),
+    );
+    app.world.entity_mut(entity).insert(RequestClose);
+    run(&mut app, 1);
+    assert!(app.world.entity(entity).contains::<Closing>());
+
+    fake.withdraw_window(window);
+    run(&mut app, 2);
+    assert!(!kills(&fake.requests(), window));
+    let entity = app.world.entity(entity);
+    assert!(!entity.contains::<Closing>());
+    assert_eq!(entity.get::<WmState>(), Some(&WmState::Withdrawn));
+}
+
+fn kills(requests: &[XRequest], window: xcb::x::Window) -> bool {
+    requests
+        .iter()
+        .any(|request| matches!(request, XRequest::KillClient { window: w } if *w == window))
+}
+
+#[test]
+fn marks_focus_once_the_window_gets_it() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    app.world.entity_mut(entity).insert(RequestFocus);
+    run(&mut app, 1);
+    assert!(!app.world.entity(entity).contains::<IsFocused>());
+
+    run(&mut app, 1);
+    assert_eq!(fake.focus(), window);
+    assert!(app.world.entity(entity).contains::<IsFocused>());
+    assert_eq!(
+        fake.property(fake.root(), Atom::NetActiveWindow),
+        Some(PropertyData::U32(vec![window.resource_id()]))
+    );
+}
+
+#[test]
+fn never_focuses_windows_taking_no_input() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    // input hint set to false
+    fake.set_property(window, Atom::WmHints, PropertyData::U32(vec![1, 0]));
+    app.world.entity_mut(entity).insert(RequestFocus);
+    run(&mut app, 2);
+
+    assert_ne!(fake.focus(), window);
+    assert!(!app.world.entity(entity).contains::<IsFocused>());
+}
+
+#[test]
+fn reads_net_wm_state_set_before_mapping() {
+    let fake = FakeBackend::new();
+    fake.set_monitors(vec![MonitorInfo {
+        name: "eDP-1".into(),
+        region: Region { x: 0, y: 0, w: 1920, h: 1080 },
+        primary: true,
+        refresh_rate: None,
+    }]);
+    let mut app = app(&fake, None);
+    let window = fake.create_window(REGION, false);
+    run(&mut app, 1);
+    let requested = PropertyData::U32(vec![
+        fake.atom(Atom::NetWmStateFullscreen).resource_id(),
+        fake.atom(Atom::NetWmStateAbove).resource_id(),
+    ]);
+    fake.set_property(window, Atom::NetWmState, requested.clone());
+    run(&mut app, 1);
+    assert_eq!(fake.property(window, Atom::NetWmState), Some(requested));
+
+    let entity = entity(&app, window).unwrap();
+    fake.request_map(window);
+    run(&mut app, 2);
+    let mapped = app.world.entity(entity);
+    assert!(mapped.contains::<Fullscreen>());
+    assert_eq!(
+        mapped.get::<NetWmState>(),
+        Some(&(NetWmState::FULLSCREEN | NetWmState::ABOVE))
+    );
+    assert_eq!(fake.window(window).unwrap().region, Region {
+        x: 0,
+        y: 0,
+        w: 1920,
+        h: 1080
+    });
+}
+
+#[test]
+fn reads_window_type_set_before_mapping() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let window = fake.create_window(REGION, false);
+    let popup = fake.create_window(REGION, true);
+    // set before we selected property changes, so no PropertyNotify arrives
+    let dialog = fake.atom(Atom::NetWindowTypeDialog).resource_id();
+    fake.set_property(
+        window,
+        Atom::NetWmWindowType,
+        PropertyData::U32(vec![dialog]),
+    );
+    run(&mut app, 1);
+    let entity = entity(&app, window).unwrap();
+    assert_eq!(app.world.entity(entity).get::<WindowType>(), None);
+    assert!(fake
+        .window(window)
+        .unwrap()
+        .event_mask
+        .contains(xcb::x::EventMask::PROPERTY_CHANGE));
+    let popup = app.world.entity(self::entity(&app, popup).unwrap());
+    assert_eq!(popup.get::<WindowType>(), None);
+
+    fake.request_map(window);
+    run(&mut app, 1);
+    assert_eq!(
+        app.world.entity(entity).get::<WindowType>(),
+        Some(&WindowType::Dialog)
+    );
+
+    let utility = fake.atom(Atom::NetWindowTypeUtility).resource_id();
+    fake.set_property(
+        window,
+        Atom::NetWmWindowType,
+        PropertyData::U32(vec![utility]),
+    );
+    run(&mut app, 1);
+    assert_eq!(
+        app.world.entity(entity).get::<WindowType>(),
+        Some(&WindowType::Utility)
+    );
+}
+
+#[test]
+fn reads_size_hints_and_class_set_before_mapping() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let window = fake.create_window(REGION, false);
+    // flags for minimum and maximum size
+    let mut hints = vec![0; 18];
+    hints[0] = 1 << 4 | 1 << 5;
+    hints[5..9].copy_from_slice(&[100, 50, 400, 300]);
+    fake.set_property(window, Atom::WmNormalHints, PropertyData::U32(hints));
+    fake.set_property(
+        window,
+        Atom::WmClass,
+        PropertyData::U8(b"term\0Terminal\0".to_vec()),
+    );
+    run(&mut app, 1);
+    let entity = entity(&app, window).unwrap();
+    fake.request_map(window);
+    run(&mut app, 1);
+
+    let entity = app.world.entity(entity);
+    assert_eq!(
+        entity.get::<SizeHints>(),
+        Some(&SizeHints {
+            min: Some((100, 50)),
+            max: Some((400, 300)),
+            ..SizeHints::default()
+        })
+    );
+    assert_eq!(
+        entity.get::<WmClass>(),
+        Some(&WmClass {
+            instance: "term".into(),
+            class: "Terminal".into(),
+        })
+    );
+}
+
+#[test]
+fn updates_the_class_of_managed_windows() {
+    let fake = FakeBackend::new();
+    let mut app = app(&fake, None);
+    let (window, entity) = mapped_window(&mut app, &fake);
+    assert_eq!(app.world.entity(entity).get::<WmClass>(), None);
+
+    fake.set_property(
+        window,
+        Atom::WmClass,
+        PropertyData::U8(b"firefox\0Firefox\0".to_vec()),
+    );
+    run(&mut app, 1);
+    assert_eq!(
+        app.world.entity(entity).get::<WmClass>(),
+        Some(&WmClass {
+            instance: "firefox".into(),
+            class: "Firefox".into(),
+        })
+    );
+}
@@ -0,0 +1,3 @@
+mod lifecycle;
+mod region;
+mod size_hints;
@@ -0,0 +1,60 @@
+use crate::Region;
+
+const AREA: Region = Region { x: 10, y: 20, w: 100, h: 50 };
+
+#[test]
+fn splits_rows_covering_the_region() {
+    assert_eq!(AREA.split_rows(3), vec![
+        Region { x: 10, y: 20, w: 100, h: 16 },
+        Region { x: 10, y: 36, w: 100, h: 17 },
+        Region { x: 10, y: 53, w: 100, h: 17 },
+    ]);
+    assert_eq!(AREA.split_rows(1), vec![AREA]);
+    assert_eq!(AREA.split_rows(0), vec![]);
+}
+
+#[test]
+fn splits_columns_covering_the_region() {
+    assert_eq!(AREA.split_columns(3), vec![
+        Region { x: 10, y: 20, w: 33, h: 50 },
+        Region { x: 43, y: 20, w: 33, h: 50 },
+        Region { x: 76, y: 20, w: 34, h: 50 },
+    ]);
+}
+
+#[test]
+fn splits_by_ratios() {
+    assert_eq!(AREA.split_columns_by(&[1.0, 3.0]), vec![
+        Region { x: 10, y: 20, w: 25, h: 50 },
+        Region { x: 35, y: 20, w: 75, h: 50 },
+    ]);
+    assert_eq!(AREA.split_rows_by(&[0.2, 0.2, 0.6]), vec![
+        Region { x: 10, y: 20, w: 100, h: 10 },
+        Region { x: 10, y: 30, w: 100, h: 10 },
+        Region { x: 10, y: 40, w: 100, h: 30 },
+    ]);
+}
+
+#[test]
+fn splits_by_ratios_without_gaps_or_overlaps() {
+    let regions = AREA.split_columns_by(&[1.0, 1.0, 1.0]);
+    assert_eq!(regions.iter().map(|r| r.w).sum::<u32>(), AREA.w);
+    for pair in regions.windows(2) {
+        assert_eq!(pair[0].x + pair[0].w as i32, pair[1].x);
+    }
+}
+
+#[test]
+fn splits_by_zero_ratios_into_empty_regions() {
+    assert_eq!(AREA.split_rows_by(&[0.0, 0.0]), vec![
+        Region { h: 0, ..AREA },
+        Region { h: 0, ..AREA },
+    ]);
+}
+
+#[test]
+fn shrinks_without_underflowing() {
+    assert_eq!(AREA.shrink(5, 5), Region { x: 15, y: 25, w: 90, h: 40 });
+    assert_eq!(AREA.shrink(2, 3), Region { x: 12, y: 22, w: 95, h: 45 });
+    assert_eq!(AREA.shrink(30, 30), Region { x: 40, y: 50, w: 40, h: 0 });
+}
@@ -0,0 +1,110 @@
+use crate::component::{IgnoreSizeHints, SizeHints};
+use crate::Region;
+
+const AREA: Region = Region { x: 0, y: 0, w: 960, h: 1080 };
+
+#[test]
+fn steps_by_increments_from_the_base_size() {
+    let hints = SizeHints {
+        min: Some((100, 50)),
+        base: Some((4, 4)),
+        increment: Some((7, 13)),
+        gravity: Some(5),
+        ..SizeHints::default()
+    };
+    assert_eq!(hints.constrain(AREA, IgnoreSizeHints::empty()), Region {
+        x: 2,
+        y: 5,
+        w: 956,
+        h: 1070
+    });
+}
+
+#[test]
+fn caps_at_the_maximum_size_in_the_north_west() {
+    let hints = SizeHints {
+        max: Some((300, 200)),
+        ..SizeHints::default()
+    };
+    assert_eq!(hints.constrain(AREA, IgnoreSizeHints::empty()), Region {
+        w: 300,
+        h: 200,
+        ..AREA
+    });
+}
+
+#[test]
+fn places_by_gravity() {
+    let hints = SizeHints {
+        max: Some((300, 200)),
+        gravity: Some(9),
+        ..SizeHints::default()
+    };
+    assert_eq!(hints.constrain(AREA, IgnoreSizeHints::empty()), Region {
+        x: 660,
+        y: 880,
+        w: 300,
+        h: 200
+    });
+}
+
+#[test]
+fn keeps_the_aspect_ratio() {
+    let square = SizeHints {
+        aspect: Some(((1, 1), (1, 1))),
+        ..SizeHints::default()
+    };
+    assert_eq!(square.constrain(AREA, IgnoreSizeHints::empty()), Region {
+        w: 960,
+        h: 960,
+        ..AREA
+    });
+    let wide = SizeHints {
+        aspect: Some(((16, 9), (16, 9))),
+        ..SizeHints::default()
+    };
+    assert_eq!(wide.constrain(AREA, IgnoreSizeHints::empty()), Region {
+        w: 960,
+        h: 540,
+        ..AREA
+    });
+}
+
+#[test]
+fn grows_past_the_region_to_the_minimum_size() {
+    let hints = SizeHints {
+        min: Some((1000, 50)),
+        ..SizeHints::default()
+    };
+    assert_eq!(hints.constrain(AREA, IgnoreSizeHints::empty()), Region {
+        w: 1000,
+        ..AREA
+    });
+}
+
+#[test]
+fn fills_the_region_without_hints() {
+    assert_eq!(
+        SizeHints::default().constrain(AREA, IgnoreSizeHints::empty()),
+        AREA
+    );
+}
+
+#[test]
+fn leaves_out_ignored_constraints() {
+    let hints = SizeHints {
+        min: Some((100, 50)),
+        max: Some((900, 2000)),
+        increment: Some((7, 13)),
+        ..SizeHints::default()
+    };
+    let ignored = IgnoreSizeHints::INCREMENT;
+    assert_eq!(hints.constrain(AREA, ignored), Region { w: 900, ..AREA });
+    let small = Region { w: 10, h: 10, ..AREA };
+    assert_eq!(hints.constrain(small, ignored), Region {
+        w: 100,
+        h: 50,
+        ..AREA
+    });
+    assert_eq!(hints.constrain(small, IgnoreSizeHints::all()), small);
+}
@@ -0,0 +1,43 @@
+use std::marker::PhantomData;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
+use rustc_hash::FxHashMap as HashMap;
+
+/// Maps xcb window ids to their window entities, kept in sync as window
+/// entities get spawned and despawned
+#[derive(Default, Debug)]
+pub struct WindowIndex(HashMap<xcb::x::Window, Entity>);
+
+impl WindowIndex {
+    pub fn get(&self, window: xcb::x::Window) -> Option<Entity> {
+        self.0.get(&window).copied()
+    }
+
+    pub(crate) fn insert(&mut self, window: xcb::x::Window, entity: Entity) {
+        self.0.insert(window, entity);
+    }
+
+    pub(crate) fn remove(&mut self, window: xcb::x::Window) -> Option<Entity> {
+        self.0.remove(&window)
+    }
+}
+
+/// System parameter for looking up window entities by their xcb window id
+///
+/// `Update` systems using it should run after
+/// [`crate::XcbSystem::DespawnWindows`] to not see windows destroyed in the
+/// current frame.
+#[derive(SystemParam)]
+pub struct EntityLookup<'w, 's> {
+    index: Res<'w, WindowIndex>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> EntityLookup<'w, 's> {
+    /// Returns the entity of the window with the given xcb id
+    pub fn get(&self, window: xcb::x::Window) -> Option<Entity> {
+        self.index.get(window)
+    }
+}
@@ -0,0 +1,33 @@
+use crate::atom::Atom;
+use crate::backend::PropertyData;
+use crate::component::*;
+use crate::event as ev;
+use crate::property::WindowProperty;
+use crate::xconn::XConn;
+
+/// The first type in `_NET_WM_WINDOW_TYPE` we know wins, transient windows
+/// without one are dialogs
+impl WindowProperty for WindowType {
+    const ATOMS: &'static [Atom] = &[Atom::NetWmWindowType, Atom::WmTransientFor];
+
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError> {
+        if let Some(PropertyData::U32(atoms)) =
+            xconn.get_property(window, xconn.atom(Atom::NetWmWindowType))?
+        {
+            let window_type = atoms
+                .into_iter()
+                .filter_map(|atom| xconn.lookup_atom_id(atom))
+                .find_map(WindowType::from_atom);
+            if window_type.is_some() {
+                return Ok(window_type);
+            }
+        }
+
+        let transient_for = xconn.get_property(window, xconn.atom(Atom::WmTransientFor))?;
+        if matches!(transient_for, Some(PropertyData::U32(windows)) if !windows.is_empty()) {
+            Ok(Some(WindowType::Dialog))
+        } else {
+            Ok(Some(WindowType::Normal))
+        }
+    }
+}
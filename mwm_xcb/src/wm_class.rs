@@ -0,0 +1,25 @@
+use crate::backend::PropertyData;
+use crate::component::*;
+use crate::property::WindowProperty;
+use crate::xconn::XConn;
+use crate::{event as ev, Atom};
+
+/// The property holds two null terminated strings, the instance and the class
+/// name
+impl WindowProperty for WmClass {
+    const ATOMS: &'static [Atom] = &[Atom::WmClass];
+
+    fn read(xconn: &XConn, window: xcb::x::Window) -> Result<Option<Self>, ev::XError> {
+        let bytes = match xconn.get_property(window, xconn.atom(Atom::WmClass))? {
+            Some(PropertyData::U8(bytes)) => bytes,
+            _ => return Ok(None),
+        };
+        let mut names = bytes
+            .split(|&b| b == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned());
+        Ok(match (names.next(), names.next()) {
+            (Some(instance), Some(class)) => Some(WmClass { instance, class }),
+            _ => None,
+        })
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::Context;
+use std::time::Instant;
+
 use bevy_ecs::prelude::*;
 use log::{trace, warn};
 use xcb::{randr, x};
@@ -6,28 +7,38 @@ use xcb::{randr, x};
 use crate::event as ev;
 use crate::xconn::XConn;
 
-/// Polls as many XCB events as are in the queue
-pub fn _poll_xcb_events(xconn: Res<XConn>) -> Vec<xcb::Event> {
-    let mut buf = Vec::new();
-    while let Some(ev) = xconn.conn.poll_for_event().expect("xcb error") {
-        buf.push(ev);
+/// Earliest point in time some system needs the event loop to run again at,
+/// bounds how long [`wait_for_xcb_events`] blocks. Systems still waiting have
+/// to ask again every frame
+#[derive(Default, Debug)]
+pub struct WakeUp(Option<Instant>);
+
+impl WakeUp {
+    /// Makes sure the event loop runs again no later than `at`
+    pub fn at(&mut self, at: Instant) {
+        self.0 = Some(self.0.map_or(at, |wake_up| wake_up.min(at)));
     }
-    buf
 }
 
-/// Blocks until at least one XCB event arrives and then polls as many as are in
-/// the queue
+/// Polls as many XCB events as are in the queue
+pub fn _poll_xcb_events(xconn: Res<XConn>) -> Vec<Result<xcb::Event, ev::XError>> {
+    xconn.poll_for_events()
+}
+
+/// Blocks until at least one XCB event or error arrives, or until the
+/// requested [`WakeUp`], and then polls as many as are in the queue
 ///
 /// Uses `ResMut` even though it only needs shared access to force blocking the
 /// bevy event loop.
-pub fn wait_for_xcb_events(xconn: ResMut<XConn>) -> Vec<xcb::Event> {
-    let mut buf = Vec::with_capacity(1);
-    let ev = xconn.conn.wait_for_event().expect("xcb error");
-    buf.push(ev);
-    while let Some(ev) = xconn.conn.poll_for_queued_event().expect("xcb error") {
-        buf.push(ev);
-    }
-    buf
+pub fn wait_for_xcb_events(
+    xconn: ResMut<XConn>,
+    mut wake_up: ResMut<WakeUp>,
+) -> Vec<Result<xcb::Event, ev::XError>> {
+    let timeout = wake_up
+        .0
+        .take()
+        .map(|at| at.saturating_duration_since(Instant::now()));
+    xconn.wait_for_events(timeout)
 }
 
 /// Blocks until all buffered XCB requests are sent
@@ -35,12 +46,13 @@ pub fn wait_for_xcb_events(xconn: ResMut<XConn>) -> Vec<xcb::Event> {
 /// Uses `ResMut` even though it only needs shared access to force blocking the
 /// bevy event loop.
 pub fn flush_xcb(xconn: ResMut<XConn>) {
-    xconn.conn.flush().context("flush").unwrap();
+    xconn.flush();
 }
 
-/// Dispatches XCB events into their individual `EventWriter`s
+/// Dispatches XCB events into their individual `EventWriter`s and errors into
+/// the [`ev::XError`] `EventWriter`
 pub fn process_xcb_events(
-    In(events): In<Vec<xcb::Event>>,
+    In(events): In<Vec<Result<xcb::Event, ev::XError>>>,
     // xcb::x events
     (
         mut ev_key_press,
@@ -117,8 +129,18 @@ pub fn process_xcb_events(
     // xcb::randr events
     mut ev_screen_change_notify: EventWriter<ev::ScreenChangeNotify>,
     mut ev_notify: EventWriter<ev::Notify>,
+
+    mut ev_x_error: EventWriter<ev::XError>,
 ) {
     for event in events.into_iter() {
+        let event = match event {
+            Ok(event) => event,
+            Err(error) => {
+                warn!("received error {error:?}");
+                ev_x_error.send(error);
+                continue;
+            },
+        };
         trace!("received event {event:?}");
         match event {
             xcb::Event::X(event) => match event {
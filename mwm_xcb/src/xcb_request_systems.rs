@@ -1,37 +1,113 @@
+use std::time::Instant;
+
 use bevy_ecs::prelude::*;
 use log::debug;
+use xcb::Xid;
 
+use crate::atom::Atom;
+use crate::backend::{PropertyData, XRequest};
 use crate::component::*;
+use crate::plugin::CloseTimeout;
 use crate::request::*;
 use crate::xconn::XConn;
+use crate::{event as ev, WakeUp};
+
+/// Checks whether the window's client lists the protocol in its
+/// `WM_PROTOCOLS`
+pub(crate) fn supports_protocol(
+    xconn: &XConn,
+    window: xcb::x::Window,
+    protocol: Atom,
+) -> Result<bool, ev::XError> {
+    let protocols = xconn.get_property(window, xconn.atom(Atom::WmProtocols))?;
+    let protocol = xconn.atom(protocol).resource_id();
+    Ok(matches!(protocols, Some(PropertyData::U32(atoms)) if atoms.contains(&protocol)))
+}
 
-/// Turn [`RequestMap`] markers into XCB requests
+/// Sets the window's `WM_STATE` property, or deletes it for
+/// [`WmState::Withdrawn`]
+pub(crate) fn set_wm_state(xconn: &XConn, window: xcb::x::Window, state: WmState) {
+    let property = xconn.atom(Atom::WmState);
+    match state {
+        WmState::Withdrawn => xconn.send(XRequest::DeleteProperty { window, property }),
+        WmState::Normal | WmState::Iconic => xconn.send(XRequest::ChangeProperty {
+            window,
+            property,
+            r#type: property,
+            // state and icon window
+            data: PropertyData::U32(vec![state as u32, 0]),
+        }),
+    }
+}
+
+/// Turn [`RequestMap`] markers into XCB requests. Managed windows also get
+/// their [`WmState`] and [`NetWmState::HIDDEN`] updated, [`RequestMap::Unmap`]
+/// iconifies them
 pub fn process_request_map(
     xconn: Res<XConn>,
-    query: Query<(Entity, &Window, &RequestMap, Option<&IsMapped>), Added<RequestMap>>,
+    mut query: Query<
+        (
+            Entity,
+            &Window,
+            &RequestMap,
+            Option<&IsMapped>,
+            Option<&IsManaged>,
+            Option<&mut NetWmState>,
+        ),
+        Added<RequestMap>,
+    >,
     mut commands: Commands,
 ) {
-    for (entity, &Window(window), request, is_mapped) in query.iter() {
+    for (entity, &Window(window), request, is_mapped, is_managed, mut state) in query.iter_mut() {
+        let mut entity = commands.entity(entity);
         match (is_mapped.is_some(), request) {
             (false, RequestMap::Map) => {
-                // TODO error handling
                 debug!("mapping window {window:?}");
-                xconn.conn.send_request(&xcb::x::MapWindow { window });
+                xconn.send(XRequest::MapWindow { window });
+                if is_managed.is_some() {
+                    set_wm_state(&xconn, window, WmState::Normal);
+                    entity.insert(WmState::Normal);
+                    if let Some(state) = state.as_mut().filter(|s| s.contains(NetWmState::HIDDEN)) {
+                        state.remove(NetWmState::HIDDEN);
+                    }
+                }
             },
             (true, RequestMap::Unmap) => {
-                // TODO error handling
                 debug!("unmapping window {window:?}");
-                xconn.conn.send_request(&xcb::x::UnmapWindow { window });
+                xconn.send(XRequest::UnmapWindow { window });
+                if is_managed.is_some() {
+                    set_wm_state(&xconn, window, WmState::Iconic);
+                    entity.insert(WmState::Iconic);
+                    if let Some(state) = &mut state {
+                        state.insert(NetWmState::HIDDEN);
+                    }
+                }
             },
             _ => {
                 // skip windows which are already in the requested state
             },
         }
-        commands.entity(entity).remove::<RequestMap>();
+        entity.remove::<RequestMap>();
+    }
+}
+
+/// Deletes the `WM_STATE` property of windows which got withdrawn by their
+/// clients
+pub fn process_withdrawn_windows(
+    xconn: Res<XConn>,
+    query: Query<(&Window, &WmState), Changed<WmState>>,
+) {
+    for (&Window(window), &state) in query.iter() {
+        if state == WmState::Withdrawn {
+            debug!("withdrawing window {window:?}");
+            set_wm_state(&xconn, window, state);
+        }
     }
 }
 
-/// Turn [`RequestSize`] and [`RequestBorder`] markers into XCB requests
+/// Turn [`RequestSize`] and [`RequestBorder`] markers into XCB requests.
+/// Windows marked [`RequestConfigureNotify`] which don't get resized are sent
+/// a synthetic `ConfigureNotify` with their resulting geometry
 // TODO also handle window borders, sibling and stackmode (if/when we need those
 // in the future) in the same system as the xcb configure request can handle all
 // at once
@@ -39,20 +115,38 @@ pub fn process_request_resize(
     xconn: Res<XConn>,
     query: Query<
         (
+            Entity,
             &Window,
             Option<&RequestSize>,
             &Size,
             Option<&RequestBorder>,
             &Border,
+            Option<&RequestConfigureNotify>,
         ),
-        Or<(Added<RequestSize>, Added<RequestBorder>)>,
+        Or<(
+            Changed<RequestSize>,
+            Changed<RequestBorder>,
+            Added<RequestConfigureNotify>,
+        )>,
     >,
+    mut commands: Commands,
 ) {
-    for (&Window(window), request_size, Size(size), request_border, Border(border)) in query.iter()
+    for (
+        entity,
+        &Window(window),
+        request_size,
+        Size(size),
+        request_border,
+        Border(border),
+        configure_notify,
+    ) in query.iter()
     {
         let mut cmd = Vec::new();
+        let mut region = *size;
+        let mut border = *border;
 
-        if let Some(RequestSize(request)) = request_size {
+        if let Some(&RequestSize(request)) = request_size {
+            region = request;
             if request.x != size.x {
                 cmd.push(xcb::x::ConfigWindow::X(request.x));
             }
@@ -66,18 +160,161 @@ pub fn process_request_resize(
                 cmd.push(xcb::x::ConfigWindow::Height(request.h));
             }
         }
-        if let Some(RequestBorder(request)) = request_border {
+        if let Some(&RequestBorder(request)) = request_border {
             if request != border {
-                cmd.push(xcb::x::ConfigWindow::BorderWidth((*request).into()));
+                cmd.push(xcb::x::ConfigWindow::BorderWidth(request.into()));
             }
+            border = request;
         }
 
+        // NOTE ICCCM only lets the real ConfigureNotify stand in for resizes
+        let resized = cmd.iter().any(|value| {
+            matches!(
+                value,
+                xcb::x::ConfigWindow::Width(_)
+                    | xcb::x::ConfigWindow::Height(_)
+                    | xcb::x::ConfigWindow::BorderWidth(_)
+            )
+        });
         if !cmd.is_empty() {
-            // TODO error handling
             debug!("configuring window {window:?} with {cmd:?}");
-            xconn
-                .conn
-                .send_request(&xcb::x::ConfigureWindow { window, value_list: cmd.as_slice() });
+            xconn.send(XRequest::ConfigureWindow { window, values: cmd });
+        }
+        if configure_notify.is_some() {
+            commands.entity(entity).remove::<RequestConfigureNotify>();
+            if !resized {
+                debug!("notifying window {window:?} of its geometry {region:?}");
+                xconn.send(XRequest::SendConfigureNotify { window, region, border });
+            }
         }
     }
 }
+
+/// Turn [`RequestClose`] markers into requests for the clients to close their
+/// windows. Clients supporting `WM_DELETE_WINDOW` get asked to and their
+/// windows marked [`Closing`] if a close timeout is configured, other clients
+/// get killed right away
+pub fn process_request_close(
+    xconn: Res<XConn>,
+    close_timeout: Res<CloseTimeout>,
+    query: Query<(Entity, &Window), Added<RequestClose>>,
+    mut ev_x_error: EventWriter<ev::XError>,
+    mut commands: Commands,
+) {
+    for (entity, &Window(window)) in query.iter() {
+        let mut entity = commands.entity(entity);
+        entity.remove::<RequestClose>();
+
+        match supports_protocol(&xconn, window, Atom::WmDeleteWindow) {
+            Ok(true) => {
+                debug!("asking window {window:?} to close");
+                xconn.send(XRequest::SendClientMessage {
+                    window,
+                    r#type: xconn.atom(Atom::WmProtocols),
+                    data: [
+                        xconn.atom(Atom::WmDeleteWindow).resource_id(),
+                        xcb::x::CURRENT_TIME,
+                        0,
+                        0,
+                        0,
+                    ],
+                });
+                if let CloseTimeout(Some(timeout)) = *close_timeout {
+                    entity.insert(Closing(Instant::now() + timeout));
+                }
+            },
+            Ok(false) => {
+                debug!("killing client of window {window:?}");
+                xconn.send(XRequest::KillClient { window });
+            },
+            Err(error) => ev_x_error.send(error),
+        }
+    }
+}
+
+/// Kills the clients of [`Closing`] windows which are still around after their
+/// deadline
+pub fn kill_unresponsive_windows(
+    xconn: Res<XConn>,
+    mut wake_up: ResMut<WakeUp>,
+    query: Query<(Entity, &Window, &Closing)>,
+    mut commands: Commands,
+) {
+    let now = Instant::now();
+    for (entity, &Window(window), &Closing(deadline)) in query.iter() {
+        if deadline <= now {
+            debug!("killing unresponsive client of window {window:?}");
+            xconn.send(XRequest::KillClient { window });
+            commands.entity(entity).remove::<Closing>();
+        } else {
+            wake_up.at(deadline);
+        }
+    }
+}
+
+/// Selects the events we need from a managed window, before reading any of its
+/// properties so no change to them goes unnoticed
+pub(crate) fn select_window_events(xconn: &XConn, window: xcb::x::Window) {
+    xconn.send(XRequest::SelectInput {
+        window,
+        event_mask: xcb::x::EventMask::FOCUS_CHANGE | xcb::x::EventMask::PROPERTY_CHANGE,
+    });
+}
+
+/// Turn [`RequestFocus`] markers of mapped windows into XCB requests following
+/// the ICCCM input models. The [`IsFocused`] marker and `_NET_ACTIVE_WINDOW`
+/// only follow once the `FocusIn` arrives, windows taking no input never get
+/// focus
+pub fn process_request_focus(
+    xconn: Res<XConn>,
+    query: Query<(Entity, &Window), (With<RequestFocus>, With<IsMapped>)>,
+    mut ev_x_error: EventWriter<ev::XError>,
+    mut commands: Commands,
+) {
+    for (entity, &Window(window)) in query.iter() {
+        commands.entity(entity).remove::<RequestFocus>();
+
+        let (accepts_input, takes_focus) = match input_model(&xconn, window) {
+            Ok(model) => model,
+            Err(error) => {
+                ev_x_error.send(error);
+                continue;
+            },
+        };
+        debug!("focusing window {window:?}");
+        if accepts_input {
+            xconn.send(XRequest::SetInputFocus { window });
+        }
+        if takes_focus {
+            xconn.send(XRequest::SendClientMessage {
+                window,
+                r#type: xconn.atom(Atom::WmProtocols),
+                data: [
+                    xconn.atom(Atom::WmTakeFocus).resource_id(),
+                    xcb::x::CURRENT_TIME,
+                    0,
+                    0,
+                    0,
+                ],
+            });
+        }
+    }
+}
+
+/// Whether the window accepts input focus set by the window manager, per its
+/// `WM_HINTS` input flag, and whether it takes focus itself via
+/// `WM_TAKE_FOCUS`
+fn input_model(xconn: &XConn, window: xcb::x::Window) -> Result<(bool, bool), ev::XError> {
+    const INPUT_HINT: u32 = 1;
+
+    let hints = xconn.get_property(window, xconn.atom(Atom::WmHints))?;
+    let accepts_input = match hints {
+        Some(PropertyData::U32(hints)) if hints.len() >= 2 && hints[0] & INPUT_HINT != 0 => {
+            hints[1] != 0
+        },
+        // clients not setting the hint are assumed to want input
+        _ => true,
+    };
+    let takes_focus = supports_protocol(xconn, window, Atom::WmTakeFocus)?;
+    Ok((accepts_input, takes_focus))
+}
@@ -1,20 +1,83 @@
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
 use anyhow::{Context, Result};
+use log::warn;
 use rustc_hash::FxHashMap as HashMap;
+use xcb::{Reply, Xid};
 
 use crate::atom::Atom;
+use crate::backend::{Backend, ExistingWindow, MonitorInfo, PropertyData, XRequest};
+use crate::event::{XError, XErrorKind};
+use crate::Region;
 
-/// data for abstracting communication with the X server via xcb
+/// Resource through which the ECS systems communicate with the X server
+///
+/// Wraps a [`Backend`], by default an xcb connection. Insert one with the
+/// `FakeBackend` of the `fake` feature before adding the [`crate::XcbPlugin`]
+/// to run without an X server.
 pub struct XConn {
-    pub(crate) conn: xcb::Connection,
+    backend: Box<dyn Backend>,
+    // interned atoms by id, for the ones read from properties and messages
+    atoms: HashMap<xcb::x::Atom, Atom>,
+}
+
+impl XConn {
+    pub fn new(backend: impl Backend) -> Self {
+        let atoms = Atom::ALL
+            .iter()
+            .map(|&atom| (backend.atom(atom), atom))
+            .collect();
+        XConn { backend: Box::new(backend), atoms }
+    }
+
+    /// Establish a new connection to the running X server. Fails if unable to
+    /// connect
+    pub(super) fn init() -> Result<XConn> {
+        XcbBackend::connect().map(XConn::new)
+    }
+
+    /// Looks up an interned atom among the ones we know
+    pub fn lookup_atom(&self, atom: xcb::x::Atom) -> Option<Atom> {
+        if atom.is_none() {
+            return None;
+        }
+        self.atoms.get(&atom).copied()
+    }
+
+    /// Looks up an atom id as found in `ATOM` properties and client messages
+    /// among the interned atoms we know
+    pub fn lookup_atom_id(&self, id: u32) -> Option<Atom> {
+        // SAFETY the id is only compared against interned atoms, never sent
+        self.lookup_atom(unsafe { xcb::XidNew::new(id) })
+    }
+}
 
-    pub(crate) root: xcb::x::Window,
-    pub(crate) check_win: xcb::x::Window,
+impl Deref for XConn {
+    type Target = dyn Backend;
+
+    fn deref(&self) -> &Self::Target {
+        self.backend.as_ref()
+    }
+}
+
+/// data for abstracting communication with the X server via xcb
+struct XcbBackend {
+    conn: xcb::Connection,
+
+    root: xcb::x::Window,
+    check_win: xcb::x::Window,
+
+    // optional extensions the X server supports
+    has_randr: bool,
+    has_xinerama: bool,
 
     // interned atoms
-    pub(crate) atoms: HashMap<Atom, xcb::x::Atom>,
+    atoms: HashMap<Atom, xcb::x::Atom>,
 }
 
-impl Drop for XConn {
+impl Drop for XcbBackend {
     fn drop(&mut self) {
         // release any of the keybindings we are holding on to
         self.conn.send_request(&xcb::x::UngrabKey {
@@ -27,23 +90,37 @@ impl Drop for XConn {
         self.conn
             .send_request(&xcb::x::DestroyWindow { window: self.check_win });
 
-        // mark ourselves as no longer being the active root window
-        self.conn.send_request(&xcb::x::DeleteProperty {
-            window: self.root,
-            property: self.atom_id(Atom::NetActiveWindow),
-        });
+        // stop advertising ourselves as the EWMH compliant window manager
+        for atom in [
+            Atom::NetActiveWindow,
+            Atom::NetSupportingWmCheck,
+            Atom::NetSupported,
+        ] {
+            self.conn.send_request(&xcb::x::DeleteProperty {
+                window: self.root,
+                property: self.atom(atom),
+            });
+        }
 
         self.conn.flush().unwrap();
     }
 }
 
-impl XConn {
+impl XcbBackend {
     /// Establish a new connection to the running X server. Fails if unable to
     /// connect
-    pub(super) fn init() -> Result<XConn> {
-        let (conn, _) =
-            xcb::Connection::connect_with_extensions(None, &[xcb::Extension::RandR], &[])
-                .context("connecting to X server")?;
+    fn connect() -> Result<XcbBackend> {
+        let (conn, _) = xcb::Connection::connect_with_extensions(None, &[], &[
+            xcb::Extension::RandR,
+            xcb::Extension::Xinerama,
+        ])
+        .context("connecting to X server")?;
+        let has_randr = conn
+            .active_extensions()
+            .any(|extension| extension == xcb::Extension::RandR);
+        let has_xinerama = conn
+            .active_extensions()
+            .any(|extension| extension == xcb::Extension::Xinerama);
 
         let root = conn
             .get_setup()
@@ -83,9 +160,11 @@ impl XConn {
             value_list: &[],
         });
 
-        let select_input_cookie = conn.send_request_checked(&xcb::randr::SelectInput {
-            window: root,
-            enable: xcb::randr::NotifyMask::CRTC_CHANGE | xcb::randr::NotifyMask::SCREEN_CHANGE,
+        let select_input_cookie = has_randr.then(|| {
+            conn.send_request_checked(&xcb::randr::SelectInput {
+                window: root,
+                enable: xcb::randr::NotifyMask::CRTC_CHANGE | xcb::randr::NotifyMask::SCREEN_CHANGE,
+            })
         });
 
         let substructure_redirect_cookie =
@@ -113,15 +192,481 @@ impl XConn {
         };
         conn.check_request(create_window_cookie)
             .context("create check window")?;
-        conn.check_request(select_input_cookie)
-            .context("select input")?;
+        if let Some(select_input_cookie) = select_input_cookie {
+            conn.check_request(select_input_cookie)
+                .context("select input")?;
+        }
         conn.check_request(substructure_redirect_cookie)
             .context("substructure redirect")?;
 
-        Ok(XConn { conn, root, check_win, atoms })
+        Ok(XcbBackend {
+            conn,
+            root,
+            check_win,
+            has_randr,
+            has_xinerama,
+            atoms,
+        })
     }
 
-    fn atom_id(&self, atom: Atom) -> xcb::x::Atom {
+    /// Queries the outputs driving a CRTC through RandR
+    fn randr_monitors(&self) -> Result<Vec<MonitorInfo>, XError> {
+        use xcb::randr;
+
+        let resources = protocol_result(
+            self.conn.wait_for_reply(
+                self.conn
+                    .send_request(&randr::GetScreenResourcesCurrent { window: self.root }),
+            ),
+        )?;
+        let primary = protocol_result(
+            self.conn.wait_for_reply(
+                self.conn
+                    .send_request(&randr::GetOutputPrimary { window: self.root }),
+            ),
+        )?
+        .output();
+        let config_timestamp = resources.config_timestamp();
+
+        let output_cookies = resources
+            .outputs()
+            .iter()
+            .map(|&output| {
+                let cookie = self
+                    .conn
+                    .send_request(&randr::GetOutputInfo { output, config_timestamp });
+                (output, cookie)
+            })
+            .collect::<Vec<_>>();
+
+        let mut monitors = Vec::new();
+        let mut crtcs = Vec::new();
+        for (output, cookie) in output_cookies {
+            let info = protocol_result(self.conn.wait_for_reply(cookie))?;
+            let crtc = info.crtc();
+            if info.connection() != randr::Connection::Connected
+                || crtc.is_none()
+                || crtcs.contains(&crtc)
+            {
+                continue;
+            }
+            crtcs.push(crtc);
+
+            let crtc_info = protocol_result(
+                self.conn.wait_for_reply(
+                    self.conn
+                        .send_request(&randr::GetCrtcInfo { crtc, config_timestamp }),
+                ),
+            )?;
+            let refresh_rate = resources
+                .modes()
+                .iter()
+                .find(|mode| mode.id == crtc_info.mode().resource_id())
+                .and_then(refresh_rate);
+            monitors.push(MonitorInfo {
+                name: String::from_utf8_lossy(info.name()).into_owned(),
+                region: Region {
+                    x: crtc_info.x().into(),
+                    y: crtc_info.y().into(),
+                    w: crtc_info.width().into(),
+                    h: crtc_info.height().into(),
+                },
+                primary: output == primary,
+                refresh_rate,
+            });
+        }
+        Ok(monitors)
+    }
+
+    /// Queries the screens Xinerama combines into the root window, they have
+    /// no names so they are named after their position in the list
+    fn xinerama_monitors(&self) -> Result<Vec<MonitorInfo>, XError> {
+        let is_active = protocol_result(
+            self.conn
+                .wait_for_reply(self.conn.send_request(&xcb::xinerama::IsActive {})),
+        )?;
+        if is_active.state() == 0 {
+            return Ok(Vec::new());
+        }
+        let screens = protocol_result(
+            self.conn
+                .wait_for_reply(self.conn.send_request(&xcb::xinerama::QueryScreens {})),
+        )?;
+        let monitors = screens
+            .screen_info()
+            .iter()
+            .enumerate()
+            .map(|(i, screen)| MonitorInfo {
+                name: format!("xinerama-{i}"),
+                region: Region {
+                    x: screen.x_org.into(),
+                    y: screen.y_org.into(),
+                    w: screen.width.into(),
+                    h: screen.height.into(),
+                },
+                primary: i == 0,
+                refresh_rate: None,
+            })
+            .collect();
+        Ok(monitors)
+    }
+}
+
+impl Backend for XcbBackend {
+    fn root(&self) -> xcb::x::Window {
+        self.root
+    }
+
+    fn check_window(&self) -> xcb::x::Window {
+        self.check_win
+    }
+
+    fn atom(&self, atom: Atom) -> xcb::x::Atom {
         *self.atoms.get(&atom).unwrap()
     }
+
+    fn send(&self, request: XRequest) {
+        match request {
+            XRequest::MapWindow { window } => {
+                self.conn.send_request(&xcb::x::MapWindow { window });
+            },
+            XRequest::UnmapWindow { window } => {
+                self.conn.send_request(&xcb::x::UnmapWindow { window });
+            },
+            XRequest::ConfigureWindow { window, values } => {
+                self.conn
+                    .send_request(&xcb::x::ConfigureWindow { window, value_list: &values });
+            },
+            XRequest::ChangeProperty { window, property, r#type, data } => match data {
+                PropertyData::U8(data) => {
+                    self.conn.send_request(&xcb::x::ChangeProperty {
+                        mode: xcb::x::PropMode::Replace,
+                        window,
+                        property,
+                        r#type,
+                        data: &data,
+                    });
+                },
+                PropertyData::U32(data) => {
+                    self.conn.send_request(&xcb::x::ChangeProperty {
+                        mode: xcb::x::PropMode::Replace,
+                        window,
+                        property,
+                        r#type,
+                        data: &data,
+                    });
+                },
+            },
+            XRequest::DeleteProperty { window, property } => {
+                self.conn
+                    .send_request(&xcb::x::DeleteProperty { window, property });
+            },
+            XRequest::SendClientMessage { window, r#type, data } => {
+                let event = xcb::x::ClientMessageEvent::new(
+                    window,
+                    r#type,
+                    xcb::x::ClientMessageData::Data32(data),
+                );
+                self.conn.send_request(&xcb::x::SendEvent {
+                    propagate: false,
+                    destination: xcb::x::SendEventDest::Window(window),
+                    event_mask: xcb::x::EventMask::NO_EVENT,
+                    event: &event,
+                });
+            },
+            XRequest::SendConfigureNotify { window, region, border } => {
+                let event = xcb::x::ConfigureNotifyEvent::new(
+                    window,
+                    window,
+                    xcb::x::Window::none(),
+                    region.x as i16,
+                    region.y as i16,
+                    region.w as u16,
+                    region.h as u16,
+                    border,
+                    false,
+                );
+                self.conn.send_request(&xcb::x::SendEvent {
+                    propagate: false,
+                    destination: xcb::x::SendEventDest::Window(window),
+                    event_mask: xcb::x::EventMask::STRUCTURE_NOTIFY,
+                    event: &event,
+                });
+            },
+            XRequest::KillClient { window } => {
+                self.conn
+                    .send_request(&xcb::x::KillClient { resource: window.resource_id() });
+            },
+            XRequest::SetInputFocus { window } => {
+                self.conn.send_request(&xcb::x::SetInputFocus {
+                    revert_to: xcb::x::InputFocus::PointerRoot,
+                    focus: window,
+                    time: xcb::x::CURRENT_TIME,
+                });
+            },
+            XRequest::SelectInput { window, event_mask } => {
+                self.conn.send_request(&xcb::x::ChangeWindowAttributes {
+                    window,
+                    value_list: &[xcb::x::Cw::EventMask(event_mask)],
+                });
+            },
+        }
+    }
+
+    fn flush(&self) {
+        self.conn.flush().context("flush").unwrap();
+    }
+
+    fn wait_for_events(&self, timeout: Option<Duration>) -> Vec<Result<xcb::Event, XError>> {
+        let mut buf = Vec::with_capacity(1);
+        match timeout {
+            None => buf.push(protocol_result(self.conn.wait_for_event())),
+            Some(timeout) => {
+                let mut ev = protocol_result(self.conn.poll_for_event()).transpose();
+                if ev.is_none() {
+                    // xcb can't block with a timeout, wait for the socket to become readable
+                    // instead
+                    let mut fd = libc::pollfd {
+                        fd: self.conn.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    };
+                    let timeout_ms = timeout.as_micros().div_ceil(1000);
+                    // SAFETY fd points to exactly one pollfd
+                    unsafe { libc::poll(&mut fd, 1, timeout_ms.try_into().unwrap_or(i32::MAX)) };
+                    ev = protocol_result(self.conn.poll_for_event()).transpose();
+                }
+                buf.extend(ev);
+            },
+        }
+        loop {
+            match self.conn.poll_for_queued_event() {
+                Ok(Some(ev)) => buf.push(Ok(ev)),
+                Ok(None) => break,
+                Err(err) => buf.push(Err(err.into())),
+            }
+        }
+        buf
+    }
+
+    fn poll_for_events(&self) -> Vec<Result<xcb::Event, XError>> {
+        let mut buf = Vec::new();
+        while let Some(ev) = protocol_result(self.conn.poll_for_event()).transpose() {
+            buf.push(ev);
+        }
+        buf
+    }
+
+    fn existing_windows(&self) -> Result<Vec<ExistingWindow>> {
+        let tree = self
+            .conn
+            .wait_for_reply(
+                self.conn
+                    .send_request(&xcb::x::QueryTree { window: self.root }),
+            )
+            .context("query tree")?;
+
+        // NOTE send all the requests first and only then wait for the replies, same
+        // as when interning atoms
+        #[allow(clippy::needless_collect)]
+        let cookies = tree
+            .children()
+            .iter()
+            .copied()
+            .filter(|&window| window != self.check_win)
+            .map(|window| {
+                let attributes = self
+                    .conn
+                    .send_request(&xcb::x::GetWindowAttributes { window });
+                let geometry = self.conn.send_request(&xcb::x::GetGeometry {
+                    drawable: xcb::x::Drawable::Window(window),
+                });
+                (window, attributes, geometry)
+            })
+            .collect::<Vec<_>>();
+
+        let windows = cookies
+            .into_iter()
+            .filter_map(|(window, attributes, geometry)| {
+                let attributes = self.conn.wait_for_reply(attributes).ok()?;
+                let geometry = self.conn.wait_for_reply(geometry).ok()?;
+                Some(ExistingWindow {
+                    window,
+                    region: Region {
+                        x: geometry.x().into(),
+                        y: geometry.y().into(),
+                        w: geometry.width().into(),
+                        h: geometry.height().into(),
+                    },
+                    border: geometry.border_width(),
+                    override_redirect: attributes.override_redirect(),
+                    is_mapped: attributes.map_state() != xcb::x::MapState::Unmapped,
+                })
+            })
+            .collect();
+
+        Ok(windows)
+    }
+
+    fn screen_region(&self) -> Result<Region, XError> {
+        let cookie = self.conn.send_request(&xcb::x::GetGeometry {
+            drawable: xcb::x::Drawable::Window(self.root),
+        });
+        let geometry = protocol_result(self.conn.wait_for_reply(cookie))?;
+        Ok(Region {
+            x: geometry.x().into(),
+            y: geometry.y().into(),
+            w: geometry.width().into(),
+            h: geometry.height().into(),
+        })
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, XError> {
+        // NOTE some servers (Xvfb, VNC) have RandR without any outputs, or
+        // with queries failing
+        if self.has_randr {
+            match self.randr_monitors() {
+                Ok(monitors) if !monitors.is_empty() => return Ok(monitors),
+                Ok(_) => {},
+                Err(err) => warn!("failed to query RandR monitors: {err:?}"),
+            }
+        }
+        if self.has_xinerama {
+            return self.xinerama_monitors();
+        }
+        Ok(Vec::new())
+    }
+
+    fn get_property(
+        &self,
+        window: xcb::x::Window,
+        property: xcb::x::Atom,
+    ) -> Result<Option<PropertyData>, XError> {
+        let cookie = self.conn.send_request(&xcb::x::GetProperty {
+            delete: false,
+            window,
+            property,
+            r#type: xcb::x::ATOM_ANY,
+            long_offset: 0,
+            long_length: MAX_PROPERTY_LENGTH,
+        });
+        let reply = protocol_result(self.conn.wait_for_reply(cookie))?;
+        if reply.r#type() == xcb::x::ATOM_NONE {
+            return Ok(None);
+        }
+
+        // NOTE xcb keeps the format private and panics when reading the value with the
+        // wrong one, it is the second byte of the wire reply
+        // SAFETY the raw pointer is turned right back into the reply it came from
+        let reply = unsafe {
+            let raw = reply.into_raw();
+            let format = *raw.add(1);
+            (format, xcb::x::GetPropertyReply::from_raw(raw))
+        };
+        let data = match reply {
+            (8, reply) => Some(PropertyData::U8(reply.value().to_vec())),
+            (32, reply) => Some(PropertyData::U32(reply.value().to_vec())),
+            _ => None,
+        };
+        Ok(data)
+    }
+}
+
+/// Refresh rate of the RandR mode in Hz
+fn refresh_rate(mode: &xcb::randr::ModeInfo) -> Option<f64> {
+    let mut lines = f64::from(mode.vtotal);
+    if mode.mode_flags.contains(xcb::randr::ModeFlag::DOUBLE_SCAN) {
+        lines *= 2.0;
+    }
+    if mode.mode_flags.contains(xcb::randr::ModeFlag::INTERLACE) {
+        lines /= 2.0;
+    }
+    let dots = f64::from(mode.htotal) * lines;
+    (dots > 0.0).then(|| f64::from(mode.dot_clock) / dots)
+}
+
+/// Maximum length of property values read, in 32 bit units
+const MAX_PROPERTY_LENGTH: u32 = 1 << 16;
+
+/// Separates protocol errors, which we can recover from, from connection errors
+/// which we can't
+fn protocol_result<T>(result: xcb::Result<T>) -> Result<T, XError> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(xcb::Error::Protocol(err)) => Err(err.into()),
+        Err(xcb::Error::Connection(err)) => panic!("xcb connection error: {err:?}"),
+    }
+}
+
+impl From<xcb::ProtocolError> for XError {
+    fn from(err: xcb::ProtocolError) -> Self {
+        use xcb::x::Error as E;
+
+        let (kind, request, error) = match err {
+            xcb::ProtocolError::X(error, request) => {
+                let (kind, error) = match error {
+                    E::Request(e) => (XErrorKind::Request, ErrorFields::request(&e)),
+                    E::Value(e) => (XErrorKind::Value, ErrorFields::value(&e)),
+                    E::Window(e) => (XErrorKind::Window, ErrorFields::value(&e)),
+                    E::Pixmap(e) => (XErrorKind::Pixmap, ErrorFields::value(&e)),
+                    E::Atom(e) => (XErrorKind::Atom, ErrorFields::value(&e)),
+                    E::Cursor(e) => (XErrorKind::Cursor, ErrorFields::value(&e)),
+                    E::Font(e) => (XErrorKind::Font, ErrorFields::value(&e)),
+                    E::Match(e) => (XErrorKind::Match, ErrorFields::request(&e)),
+                    E::Drawable(e) => (XErrorKind::Drawable, ErrorFields::value(&e)),
+                    E::Access(e) => (XErrorKind::Access, ErrorFields::request(&e)),
+                    E::Alloc(e) => (XErrorKind::Alloc, ErrorFields::request(&e)),
+                    E::Colormap(e) => (XErrorKind::Colormap, ErrorFields::value(&e)),
+                    E::GContext(e) => (XErrorKind::GContext, ErrorFields::value(&e)),
+                    E::IdChoice(e) => (XErrorKind::IdChoice, ErrorFields::value(&e)),
+                    E::Name(e) => (XErrorKind::Name, ErrorFields::request(&e)),
+                    E::Length(e) => (XErrorKind::Length, ErrorFields::request(&e)),
+                    E::Implementation(e) => (XErrorKind::Implementation, ErrorFields::request(&e)),
+                };
+                (kind, request, error)
+            },
+            xcb::ProtocolError::RandR(_, request) | xcb::ProtocolError::Render(_, request) => {
+                (XErrorKind::Extension, request, ErrorFields::default())
+            },
+        };
+
+        XError {
+            kind,
+            request,
+            resource_id: error.resource_id,
+            major_opcode: error.major_opcode,
+            minor_opcode: error.minor_opcode,
+            sequence: error.sequence,
+        }
+    }
+}
+
+/// Fields shared by the core protocol errors, which xcb spreads over two
+/// unrelated types
+#[derive(Default)]
+struct ErrorFields {
+    resource_id: u32,
+    major_opcode: u8,
+    minor_opcode: u16,
+    sequence: u16,
+}
+
+impl ErrorFields {
+    fn request(e: &xcb::x::RequestError) -> Self {
+        ErrorFields {
+            resource_id: e.bad_value(),
+            major_opcode: e.major_opcode(),
+            minor_opcode: e.minor_opcode(),
+            sequence: e.sequence(),
+        }
+    }
+
+    fn value(e: &xcb::x::ValueError) -> Self {
+        ErrorFields {
+            resource_id: e.bad_value(),
+            major_opcode: e.major_opcode(),
+            minor_opcode: e.minor_opcode(),
+            sequence: e.sequence(),
+        }
+    }
 }